@@ -1,7 +1,80 @@
 use bevy::prelude::*;
+use std::time::Duration;
+
+const HEADLESS_DEFAULT_LEVEL: &str = "levels/level1.level.json";
+// 30 minutes of in-game time at 60 ticks per second
+const HEADLESS_MAX_TICKS: usize = 30 * 60 * 60;
 
 fn main() {
+    let mut seed = None;
+    let mut headless = false;
+    let mut level = HEADLESS_DEFAULT_LEVEL.to_string();
+    let mut replay = None;
+
+    // `--seed <number>` replays a run, the seed is shown in the pause menu
+    // `--headless [--level <path>]` runs a level without a window and prints
+    // the outcome
+    // `--replay <path>` plays back a recorded run, every run gets recorded to
+    // `saves/last_replay.json`
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => match args.next().map(|seed| seed.parse::<u64>()) {
+                Some(Ok(s)) => seed = Some(s),
+                _ => eprintln!("--seed expects a non-negative integer"),
+            },
+            "--headless" => headless = true,
+            "--level" => match args.next() {
+                Some(path) => level = path,
+                None => {
+                    eprintln!("--level expects a path in the assets folder")
+                }
+            },
+            "--replay" => {
+                match args.next().map(goo_vs_goblins::Replay::from_file) {
+                    Some(Ok(r)) => replay = Some(r),
+                    Some(Err(err)) => {
+                        eprintln!("Failed to load replay: {}", err)
+                    }
+                    None => {
+                        eprintln!("--replay expects a path to a replay file")
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if headless {
+        let mut app =
+            goo_vs_goblins::headless_app(Duration::from_secs_f64(1. / 60.));
+        if let Some(replay) = replay {
+            match goo_vs_goblins::run_replay(&mut app, replay) {
+                Ok(summary) => println!("replay matches: {:?}", summary),
+                Err(err) => {
+                    println!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        if let Some(seed) = seed {
+            app.insert_resource(goo_vs_goblins::GameRng::from_seed(seed));
+        }
+        let outcome =
+            goo_vs_goblins::run_level(&mut app, &level, HEADLESS_MAX_TICKS);
+        println!("{}: {:?}", level, outcome);
+        let success = outcome == goo_vs_goblins::LevelOutcome::Win;
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
     let mut app = goo_vs_goblins::app();
+    if let Some(seed) = seed {
+        app.insert_resource(goo_vs_goblins::GameRng::from_seed(seed));
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(goo_vs_goblins::ReplayState::play(replay));
+    }
 
     info!("Starting launcher: Native");
     app.run();
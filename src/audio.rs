@@ -24,6 +24,9 @@ pub struct AudioAssets {
     pub mining: Handle<AudioSource>,
     pub sword_attack: Handle<AudioSource>,
     pub spawning_unit: Handle<AudioSource>,
+    pub boss_phase: Handle<AudioSource>,
+    pub loot_pickup: Handle<AudioSource>,
+    pub rare_loot: Handle<AudioSource>,
 }
 
 pub fn audio_volume_manager_system(
@@ -85,6 +88,10 @@ pub fn setup_system(
     audio_assets.mining = asset_server.load("audio/mininglooped.mp3");
     audio_assets.sword_attack = asset_server.load("audio/swordattack.mp3");
     audio_assets.spawning_unit = asset_server.load("audio/unitpoppingout.mp3");
+    // no dedicated clip yet
+    audio_assets.boss_phase = asset_server.load("audio/healercasting.mp3");
+    audio_assets.loot_pickup = asset_server.load("audio/eating.mp3");
+    audio_assets.rare_loot = asset_server.load("audio/unitpoppingout.mp3");
     *music_handler = MusicHandle(
         audio
             .play(audio_assets.soundtrack.clone())
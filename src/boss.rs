@@ -0,0 +1,428 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+use crate::{
+    audio::{AudioAssets, PlayAudioEventPositional},
+    combat::CombatComponent,
+    enemy_archetype::EnemyArchetypes,
+    enemy_logic::{spawn_enemy, EnemyId, EnemyKind},
+    game::{GameAssets, PlayerController, ResourceAssets, Velocity, ZOffset},
+    health::{hp_material, Health, HealthChangedEvent},
+    objectives::ProtectedStructure,
+    rng::GameRng,
+    worker_logic::UnitFollowPlayer,
+    GameTime, SceneState,
+};
+
+/// Diameter of `sprites/misc/circle.png`
+const CIRCLE_SPRITE_SIZE: f32 = 50.;
+/// How close a charging boss has to get to hit something
+const CHARGE_HIT_RADIUS: f32 = 100.;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum BossAbility {
+    /// Spawns `adds` around the boss
+    Summon {
+        adds: Vec<EnemyId>,
+        cooldown: f32,
+        #[serde(default = "default_summon_radius")]
+        radius: f32,
+    },
+    /// Marks a circle where the boss stands, everything still in it after
+    /// `telegraph` seconds gets hit
+    Slam {
+        radius: f32,
+        damage: f32,
+        telegraph: f32,
+        cooldown: f32,
+    },
+    /// Rushes towards its target for `duration` seconds, hitting everything
+    /// on the way once
+    Charge {
+        speed: f32,
+        duration: f32,
+        damage: f32,
+        cooldown: f32,
+    },
+    /// Applied once when the phase starts
+    Enrage {
+        damage: f32,
+        attack_speed: f32,
+        speed: f32,
+    },
+}
+
+fn default_summon_radius() -> f32 {
+    150.
+}
+
+impl BossAbility {
+    fn cooldown(&self) -> Option<f32> {
+        match self {
+            BossAbility::Summon { cooldown, .. }
+            | BossAbility::Slam { cooldown, .. }
+            | BossAbility::Charge { cooldown, .. } => Some(*cooldown),
+            BossAbility::Enrage { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BossPhaseDescriptor {
+    /// The phase starts once the health drops to this fraction of the max
+    /// health, the first phase has to start at 1
+    pub health_below: f32,
+    #[serde(default)]
+    pub abilities: Vec<BossAbility>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BossDescriptor {
+    /// Shown above the boss health bar
+    pub name: String,
+    pub phases: Vec<BossPhaseDescriptor>,
+}
+
+enum BossAction {
+    Slam {
+        position: Vec3,
+        radius: f32,
+        damage: f32,
+        telegraph: Timer,
+        indicator: Entity,
+    },
+    Charge {
+        direction: Vec3,
+        speed: f32,
+        damage: f32,
+        remaining: Timer,
+        hit: Vec<Entity>,
+    },
+}
+
+#[derive(Component)]
+pub struct Boss {
+    pub name: String,
+    phases: Vec<BossPhaseDescriptor>,
+    /// `None` until the first phase started
+    pub phase: Option<usize>,
+    /// One per ability of the current phase
+    cooldowns: Vec<Timer>,
+    /// Abilities do not overlap, a new one starts once this one is done
+    action: Option<BossAction>,
+}
+
+impl Boss {
+    pub fn new(desc: &BossDescriptor) -> Self {
+        Boss {
+            name: desc.name.clone(),
+            phases: desc.phases.clone(),
+            phase: None,
+            cooldowns: Vec::new(),
+            action: None,
+        }
+    }
+
+    fn abilities(&self) -> &[BossAbility] {
+        self.phase
+            .and_then(|phase| self.phases.get(phase))
+            .map_or(&[], |phase| phase.abilities.as_slice())
+    }
+}
+
+/// Moves bosses to the next phase once their health gets low enough
+fn boss_phase_system(
+    mut bosses: Query<(
+        &mut Boss,
+        &Health,
+        &GlobalTransform,
+        &mut Velocity,
+        &mut TextureAtlasSprite,
+        Option<&mut CombatComponent>,
+    )>,
+    audio_assets: Res<AudioAssets>,
+    mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+    mut cmd: Commands,
+) {
+    for (mut boss, health, tr, mut vel, mut sprite, mut combat) in
+        bosses.iter_mut()
+    {
+        let fraction = health.current_health / health.max_health;
+        let next = boss.phase.map_or(0, |phase| phase + 1);
+        let reached = boss
+            .phases
+            .iter()
+            .enumerate()
+            .skip(next)
+            .take_while(|(_, phase)| fraction <= phase.health_below)
+            .last()
+            .map(|(index, _)| index);
+        let phase = match reached {
+            Some(phase) => phase,
+            None => continue,
+        };
+        // the first phase starts silently
+        if boss.phase.is_some() {
+            send_audio_event.send(PlayAudioEventPositional {
+                sound: audio_assets.boss_phase.clone(),
+                position: tr.translation(),
+            });
+        }
+        let entered = next..=phase;
+        boss.phase = Some(phase);
+        // a slam cut short by the new phase leaves its telegraph behind
+        if let Some(BossAction::Slam { indicator, .. }) = boss.action.take() {
+            cmd.entity(indicator).despawn_recursive();
+        }
+        boss.cooldowns = boss
+            .abilities()
+            .iter()
+            .map(|ability| {
+                Timer::from_seconds(ability.cooldown().unwrap_or(0.), false)
+            })
+            .collect();
+
+        // skipped phases still enrage
+        let abilities = boss.phases[entered]
+            .iter()
+            .flat_map(|phase| phase.abilities.iter());
+        for ability in abilities {
+            if let BossAbility::Enrage {
+                damage,
+                attack_speed,
+                speed,
+            } = ability
+            {
+                vel.0 *= speed;
+                sprite.color = Color::rgb(1., 0.5, 0.5);
+                if let Some(combat) = combat.as_mut() {
+                    combat.damage *= damage;
+                    let duration = combat.time_between_attacks.duration();
+                    combat
+                        .time_between_attacks
+                        .set_duration(duration.div_f32(*attack_speed));
+                }
+            }
+        }
+    }
+}
+
+fn boss_ability_system(
+    mut cmd: Commands,
+    time: Res<GameTime>,
+    mut bosses: Query<(
+        Entity,
+        &mut Boss,
+        &mut Transform,
+        &EnemyKind,
+        Option<&CombatComponent>,
+    )>,
+    targets: Query<
+        (Entity, &GlobalTransform),
+        Or<(
+            With<UnitFollowPlayer>,
+            With<PlayerController>,
+            With<ProtectedStructure>,
+        )>,
+    >,
+    archetypes: Res<EnemyArchetypes>,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut rng: ResMut<GameRng>,
+    mut send_health_changed_event: EventWriter<HealthChangedEvent>,
+) {
+    for (boss_entity, mut boss, mut tr, kind, combat) in bosses.iter_mut() {
+        let boss = &mut *boss;
+        match &mut boss.action {
+            Some(BossAction::Slam {
+                position,
+                radius,
+                damage,
+                telegraph,
+                indicator,
+            }) => {
+                telegraph.tick(time.delta());
+                if !telegraph.finished() {
+                    continue;
+                }
+                for (target, target_tr) in targets.iter() {
+                    if (target_tr.translation() - *position).truncate().length()
+                        <= *radius
+                    {
+                        send_health_changed_event.send(HealthChangedEvent {
+                            target,
+                            amount: -*damage,
+                            piercing: 0.,
+                            source: Some(boss_entity),
+                        });
+                    }
+                }
+                cmd.entity(*indicator).despawn_recursive();
+                boss.action = None;
+                continue;
+            }
+            Some(BossAction::Charge {
+                direction,
+                speed,
+                damage,
+                remaining,
+                hit,
+            }) => {
+                remaining.tick(time.delta());
+                tr.translation += *direction * *speed * time.delta_seconds();
+                for (target, target_tr) in targets.iter() {
+                    if !hit.contains(&target)
+                        && (target_tr.translation() - tr.translation)
+                            .truncate()
+                            .length()
+                            <= CHARGE_HIT_RADIUS
+                    {
+                        hit.push(target);
+                        send_health_changed_event.send(HealthChangedEvent {
+                            target,
+                            amount: -*damage,
+                            piercing: 0.,
+                            source: Some(boss_entity),
+                        });
+                    }
+                }
+                if remaining.finished() {
+                    boss.action = None;
+                }
+                continue;
+            }
+            None => {}
+        }
+
+        for cooldown in boss.cooldowns.iter_mut() {
+            cooldown.tick(time.delta());
+        }
+        let ready = boss.cooldowns.iter().zip(boss.abilities()).position(
+            |(cooldown, ability)| {
+                cooldown.finished() && ability.cooldown().is_some()
+            },
+        );
+        let index = match ready {
+            Some(index) => index,
+            None => continue,
+        };
+        boss.cooldowns[index].reset();
+
+        match boss.abilities()[index].clone() {
+            BossAbility::Summon { adds, radius, .. } => {
+                let rng = &mut rng.gameplay;
+                for add in adds.iter() {
+                    let angle = rng.gen_range(0.0..TAU);
+                    let offset =
+                        Vec3::new(angle.cos(), angle.sin(), 0.) * radius;
+                    spawn_enemy(
+                        add,
+                        &mut cmd,
+                        &archetypes,
+                        tr.translation + offset,
+                        &mut *hp_assets,
+                        &mut *mesh_assets,
+                        &resource_assets,
+                        &kind.stat_scale,
+                    );
+                }
+            }
+            BossAbility::Slam {
+                radius,
+                damage,
+                telegraph,
+                ..
+            } => {
+                let position = tr.translation.truncate().extend(0.);
+                let mut indicator_tr = Transform::from_translation(position);
+                indicator_tr.scale =
+                    Vec3::splat(radius * 2. / CIRCLE_SPRITE_SIZE);
+                let indicator = cmd
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: game_assets.circle_sprite.clone(),
+                        sprite: TextureAtlasSprite {
+                            color: Color::rgba(1., 0., 0., 0.35),
+                            ..Default::default()
+                        },
+                        transform: indicator_tr,
+                        ..Default::default()
+                    })
+                    // below the units, above the background
+                    .insert(ZOffset { offset: 5000. })
+                    .insert(SlamIndicator { boss: boss_entity })
+                    .id();
+                boss.action = Some(BossAction::Slam {
+                    position,
+                    radius,
+                    damage,
+                    telegraph: Timer::from_seconds(telegraph, false),
+                    indicator,
+                });
+            }
+            BossAbility::Charge {
+                speed,
+                duration,
+                damage,
+                ..
+            } => {
+                let target = combat
+                    .and_then(|combat| combat.target)
+                    .and_then(|target| targets.get(target).ok());
+                let direction = match target {
+                    Some((_, target_tr)) => (target_tr.translation()
+                        - tr.translation)
+                        .truncate()
+                        .normalize_or_zero()
+                        .extend(0.),
+                    // nothing to charge at, wait for the next cooldown
+                    None => continue,
+                };
+                boss.action = Some(BossAction::Charge {
+                    direction,
+                    speed,
+                    damage,
+                    remaining: Timer::from_seconds(duration, false),
+                    hit: Vec::new(),
+                });
+            }
+            BossAbility::Enrage { .. } => {}
+        }
+    }
+}
+
+/// Marks where a slam is going to land
+#[derive(Component)]
+struct SlamIndicator {
+    boss: Entity,
+}
+
+/// Removes the indicators of bosses that died mid telegraph
+fn cleanup_slam_indicators_system(
+    mut cmd: Commands,
+    indicators: Query<(Entity, &SlamIndicator)>,
+    bosses: Query<(), With<Boss>>,
+) {
+    for (entity, indicator) in indicators.iter() {
+        if bosses.get(indicator.boss).is_err() {
+            cmd.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(boss_phase_system)
+                .with_system(boss_ability_system.after(boss_phase_system))
+                .with_system(cleanup_slam_indicators_system),
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::{asset::LoadState, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy_archetype::EnemyArchetypes, enemy_logic::LevelManager,
+    level::LevelAsset, merge_recipes::MergeRecipes, storage,
+    unit_stats::UnitStatsTable, GameMode, SceneState,
+};
+
+/// Campaign levels, in the order they get unlocked
+pub const CAMPAIGN_LEVELS: &[&str] = &[
+    "levels/level1.level.json",
+    "levels/level2.level.json",
+    "levels/level3.level.json",
+];
+
+const PROGRESS_FILE: &str = "progress.json";
+
+#[derive(Default)]
+pub struct Campaign {
+    pub levels: Vec<Handle<LevelAsset>>,
+    /// Index of the level the player picked
+    pub current: usize,
+}
+
+impl Campaign {
+    pub fn has_next_level(&self) -> bool {
+        self.current + 1 < self.levels.len()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CampaignProgress {
+    /// Number of levels the player can pick from the start of the campaign
+    pub unlocked_levels: usize,
+}
+
+impl Default for CampaignProgress {
+    fn default() -> Self {
+        CampaignProgress { unlocked_levels: 1 }
+    }
+}
+
+impl CampaignProgress {
+    pub fn is_unlocked(&self, index: usize) -> bool {
+        index < self.unlocked_levels
+    }
+
+    pub fn complete_level(&mut self, index: usize) {
+        if self.unlocked_levels > index + 1 {
+            return;
+        }
+        self.unlocked_levels = index + 2;
+        if let Err(err) = storage::save(PROGRESS_FILE, self) {
+            warn!("Failed to save campaign progress: {}", err);
+        }
+    }
+}
+
+/// Picks the level that gets loaded the next time we enter
+/// [`SceneState::LoadingLevel`]
+pub fn select_level(
+    index: usize,
+    campaign: &mut Campaign,
+    level_manager: &mut LevelManager,
+) {
+    campaign.current = index;
+    level_manager.level_handle = campaign.levels[index].clone();
+}
+
+fn setup_campaign(
+    asset_server: Res<AssetServer>,
+    mut campaign: ResMut<Campaign>,
+    mut progress: ResMut<CampaignProgress>,
+    mut level_manager: ResMut<LevelManager>,
+) {
+    campaign.levels = CAMPAIGN_LEVELS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
+    select_level(0, &mut campaign, &mut level_manager);
+
+    match storage::load::<CampaignProgress>(PROGRESS_FILE) {
+        Ok(Some(loaded)) => *progress = loaded,
+        Ok(None) => {}
+        Err(err) => warn!("Failed to load campaign progress: {}", err),
+    }
+}
+
+/// Waits until the selected level asset, the enemy archetypes, the merge
+/// recipes and the unit stats are available, then starts the game
+fn start_level_when_loaded_system(
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<LevelAsset>>,
+    mut level_manager: ResMut<LevelManager>,
+    mut app_state: ResMut<State<SceneState>>,
+    game_mode: Res<GameMode>,
+    archetypes: Res<EnemyArchetypes>,
+    recipes: Res<MergeRecipes>,
+    stats: Res<UnitStatsTable>,
+) {
+    if !stats.is_loaded() {
+        if matches!(
+            asset_server.get_load_state(&stats.handle),
+            LoadState::Failed
+        ) {
+            error!("Unit stats failed to load, check the logs");
+            app_state.set(SceneState::MainMenu).unwrap_or_default();
+        }
+        return;
+    }
+    if !recipes.is_loaded() {
+        if matches!(
+            asset_server.get_load_state(&recipes.handle),
+            LoadState::Failed
+        ) {
+            error!("Merge recipes failed to load, check the logs");
+            app_state.set(SceneState::MainMenu).unwrap_or_default();
+        }
+        return;
+    }
+    if !archetypes.is_loaded() {
+        if matches!(
+            asset_server.get_load_state(&archetypes.handle),
+            LoadState::Failed
+        ) {
+            error!("Enemy archetypes failed to load, check the logs");
+            app_state.set(SceneState::MainMenu).unwrap_or_default();
+        }
+        return;
+    }
+    if *game_mode == GameMode::Campaign {
+        match levels.get(&level_manager.level_handle) {
+            Some(level) => {
+                if let Err(err) = level.check_enemies(&archetypes) {
+                    error!("Selected level is invalid: {}", err);
+                    app_state.set(SceneState::MainMenu).unwrap_or_default();
+                    return;
+                }
+                if level_manager.current_level.waves.is_empty() {
+                    level_manager.current_level = level.to_level();
+                }
+            }
+            None => {
+                if matches!(
+                    asset_server.get_load_state(&level_manager.level_handle),
+                    LoadState::Failed
+                ) {
+                    error!("Selected level failed to load, check the logs");
+                    app_state.set(SceneState::MainMenu).unwrap_or_default();
+                }
+                return;
+            }
+        }
+    }
+    app_state.set(SceneState::InGame).unwrap_or_default();
+}
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Campaign::default())
+            .insert_resource(CampaignProgress::default())
+            .add_startup_system(setup_campaign)
+            .add_system_set(
+                SystemSet::on_update(SceneState::LoadingLevel)
+                    .with_system(start_level_when_loaded_system),
+            );
+    }
+}
@@ -2,17 +2,20 @@ use crate::{
     animation::{Animation, RotationAnimation},
     audio::{AudioAssets, PlayAudioEventPositional},
     easing::Easing,
+    enemy_ai::EnemyBehavior,
     enemy_logic::BasicEnemyLogic,
     game::{AvoidOthers, GameAssets, UnitType, Velocity},
     health::{Health, HealthChangedEvent},
+    loot::{BuffStat, LootBuffs},
     worker_logic::{
         HealerComponent, HealingState, TankComponent, UnitFollowPlayer,
     },
     GameTime,
 };
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AttackType {
     Melee,
     Ranged,
@@ -21,6 +24,7 @@ pub enum AttackType {
 #[derive(Component)]
 pub struct Projectile {
     target: Entity,
+    source: Entity,
     damage: f32,
     piercing: f32,
     speed: f32,
@@ -48,6 +52,13 @@ pub struct CombatComponent {
     pub target_type: UnitType,
 }
 
+/// Set on units a tank pulled onto itself, their targeting leaves them be
+/// until the taunt wears off
+#[derive(Component)]
+pub struct Taunted {
+    pub timer: Timer,
+}
+
 impl Default for CombatComponent {
     fn default() -> Self {
         CombatComponent {
@@ -205,17 +216,18 @@ fn healer_heal_component(
     }
 }
 
-fn tank_aggro_component(
+pub(crate) fn tank_aggro_component(
+    mut cmd: Commands,
     mut tanks: Query<
         (&GlobalTransform, &mut TankComponent, Entity),
         Without<BasicEnemyLogic>,
     >,
     mut enemies: Query<
-        (&mut CombatComponent, &GlobalTransform),
+        (Entity, &mut CombatComponent, &GlobalTransform),
         With<BasicEnemyLogic>,
     >,
     mut allys: Query<
-        (&mut CombatComponent, &GlobalTransform),
+        (Entity, &mut CombatComponent, &GlobalTransform),
         Without<BasicEnemyLogic>,
     >,
     time: Res<GameTime>,
@@ -224,22 +236,30 @@ fn tank_aggro_component(
         tank_comp.time_between_taunts.tick(time.delta());
         if tank_comp.time_between_taunts.just_finished() {
             tank_comp.time_between_taunts.reset();
+            // lasts until the next taunt
+            let taunt_duration = tank_comp.time_between_taunts.duration();
 
             //Spawn particles, sound
             match tank_comp.target_type {
                 UnitType::Ally => {
-                    for (mut ally_combat_comp, ally_tr) in allys.iter_mut() {
+                    for (ally, mut ally_combat_comp, ally_tr) in
+                        allys.iter_mut()
+                    {
                         if (tank_tr.translation().truncate()
                             - ally_tr.translation().truncate())
                         .length()
                             < 300.
                         {
                             ally_combat_comp.target = Some(e);
+                            cmd.entity(ally).insert(Taunted {
+                                timer: Timer::new(taunt_duration, false),
+                            });
                         }
                     }
                 }
                 UnitType::Enemy => {
-                    for (mut enemy_combat_comp, enemy_tr) in enemies.iter_mut()
+                    for (enemy, mut enemy_combat_comp, enemy_tr) in
+                        enemies.iter_mut()
                     {
                         if (tank_tr.translation().truncate()
                             - enemy_tr.translation().truncate())
@@ -247,6 +267,9 @@ fn tank_aggro_component(
                             < 300.
                         {
                             enemy_combat_comp.target = Some(e);
+                            cmd.entity(enemy).insert(Taunted {
+                                timer: Timer::new(taunt_duration, false),
+                            });
                         }
                     }
                 }
@@ -255,6 +278,20 @@ fn tank_aggro_component(
     }
 }
 
+/// Ends taunts that ran out, or whose tank is gone
+fn taunt_expiry_system(
+    mut cmd: Commands,
+    time: Res<GameTime>,
+    mut taunted: Query<(Entity, &mut Taunted, &CombatComponent)>,
+) {
+    for (entity, mut taunt, combat) in taunted.iter_mut() {
+        taunt.timer.tick(time.delta());
+        if taunt.timer.finished() || combat.target.is_none() {
+            cmd.entity(entity).remove::<Taunted>();
+        }
+    }
+}
+
 fn projectile_flying_system(
     mut cmd: Commands,
     mut projectiles: Query<(&mut Transform, &Projectile, Entity)>,
@@ -274,6 +311,7 @@ fn projectile_flying_system(
                     target: proj.target,
                     amount: -proj.damage,
                     piercing: proj.piercing,
+                    source: Some(proj.source),
                 });
                 cmd.entity(e).despawn_recursive();
             } else {
@@ -293,6 +331,7 @@ fn combat_system(
         &mut Transform,
         &Velocity,
         Entity,
+        Option<&EnemyBehavior>,
     )>,
     mut avoid_others: Query<&mut AvoidOthers>,
     transform_query: Query<&GlobalTransform>,
@@ -301,20 +340,37 @@ fn combat_system(
     mut cmd: Commands,
     audio_assets: Res<AudioAssets>,
     mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+    buffs: Res<LootBuffs>,
 ) {
-    for (mut combat_comp, mut tr, vel, e) in combatant.iter_mut() {
+    // loot buffs only help the player's units
+    let buffed = |combat_comp: &CombatComponent, stat| {
+        if matches!(combat_comp.target_type, UnitType::Enemy) {
+            buffs.multiplier(stat)
+        } else {
+            1.
+        }
+    };
+    for (mut combat_comp, mut tr, vel, e, behavior) in combatant.iter_mut() {
         if let Ok(mut avoid_other) = avoid_others.get_mut(e) {
             avoid_other.is_enabled = !combat_comp.target.is_some();
         }
         if !matches!(combat_comp.attack_state, AttackState::NotAttacking) {
             continue;
         }
-        combat_comp.time_between_attacks.tick(time.delta());
+        let attack_speed = buffed(&combat_comp, BuffStat::AttackSpeed);
+        combat_comp
+            .time_between_attacks
+            .tick(time.delta().mul_f32(attack_speed));
         let mut own_global_pos = Vec2::ZERO;
         if let Ok(global_tr) = transform_query.get(e) {
             own_global_pos = global_tr.translation().truncate();
         }
 
+        // enemies that are busy kiting or retreating move on their own
+        let (may_attack, may_approach) = behavior.map_or((true, true), |b| {
+            (b.state.may_attack(), b.state.may_approach())
+        });
+
         if let Some(target) = combat_comp.target {
             if let Ok(target_tr) = transform_query.get(target) {
                 let target_pos = target_tr.translation().truncate();
@@ -322,7 +378,8 @@ fn combat_system(
                 let distance = (target_pos - own_global_pos).length();
                 let direction = (target_pos - own_global_pos).normalize();
 
-                if combat_comp.attack_range >= distance
+                if may_attack
+                    && combat_comp.attack_range >= distance
                     && combat_comp.time_between_attacks.finished()
                 {
                     cmd.entity(e).insert(RotationAnimation(
@@ -337,7 +394,7 @@ fn combat_system(
                     combat_comp.attack_state = AttackState::AttackStart {
                         timer: Timer::from_seconds(0.3, false),
                     };
-                } else if combat_comp.attack_range < distance {
+                } else if may_approach && combat_comp.attack_range < distance {
                     tr.translation +=
                         direction.extend(0.) * time.delta_seconds() * vel.0;
                 }
@@ -345,7 +402,9 @@ fn combat_system(
         }
     }
 
-    for (mut combat_comp, tr, _, e) in combatant.iter_mut() {
+    for (mut combat_comp, tr, _, e, _) in combatant.iter_mut() {
+        let damage =
+            combat_comp.damage * buffed(&combat_comp, BuffStat::Damage);
         if let Some(target) = combat_comp.target {
             match &mut combat_comp.attack_state {
                 AttackState::AttackStart { ref mut timer } => {
@@ -377,9 +436,10 @@ fn combat_system(
                                 })
                                 .insert(Projectile {
                                     speed: 500.,
-                                    damage: combat_comp.damage,
+                                    damage,
                                     piercing: combat_comp.piercing,
                                     target: target,
+                                    source: e,
                                 })
                                 .insert(proj_transform);
                                 send_audio_event.send(
@@ -392,9 +452,10 @@ fn combat_system(
                             AttackType::Melee => {
                                 health_changed_event_writer.send(
                                     HealthChangedEvent {
-                                        amount: -combat_comp.damage,
+                                        amount: -damage,
                                         piercing: combat_comp.piercing,
                                         target: target,
+                                        source: Some(e),
                                     },
                                 );
 
@@ -446,6 +507,7 @@ impl Plugin for CombatPlugin {
         app.add_system(combat_system)
             .add_system(projectile_flying_system)
             .add_system(tank_aggro_component)
+            .add_system(taunt_expiry_system)
             .add_system(healer_heal_component);
     }
 }
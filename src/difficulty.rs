@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{enemy_logic::EnemyStatScale, storage};
+
+const DIFFICULTY_FILE: &str = "difficulty.json";
+
+/// Range of every multiplier a custom difficulty can use
+pub const MULTIPLIER_RANGE: std::ops::RangeInclusive<f32> = 0.25..=3.0;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
+pub enum DifficultyPreset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Custom,
+}
+
+impl DifficultyPreset {
+    pub const ALL: [DifficultyPreset; 4] = [
+        DifficultyPreset::Easy,
+        DifficultyPreset::Normal,
+        DifficultyPreset::Hard,
+        DifficultyPreset::Custom,
+    ];
+}
+
+/// Multipliers on top of the base enemy and economy values
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DifficultySettings {
+    pub enemy: EnemyStatScale,
+    /// Time between waves, and between spawns in endless mode
+    pub wave_delay: f32,
+    pub starting_bloodrock: f32,
+    pub unit_cost: f32,
+    pub supply_cap: f32,
+}
+
+impl Default for DifficultySettings {
+    fn default() -> Self {
+        DifficultySettings {
+            enemy: EnemyStatScale::default(),
+            wave_delay: 1.,
+            starting_bloodrock: 1.,
+            unit_cost: 1.,
+            supply_cap: 1.,
+        }
+    }
+}
+
+impl DifficultySettings {
+    /// `None` for `Custom`
+    pub fn from_preset(preset: DifficultyPreset) -> Option<Self> {
+        match preset {
+            DifficultyPreset::Easy => Some(DifficultySettings {
+                enemy: EnemyStatScale {
+                    health: 0.75,
+                    damage: 0.75,
+                    attack_speed: 0.9,
+                },
+                wave_delay: 1.3,
+                starting_bloodrock: 1.5,
+                unit_cost: 0.8,
+                supply_cap: 1.2,
+            }),
+            DifficultyPreset::Normal => Some(DifficultySettings::default()),
+            DifficultyPreset::Hard => Some(DifficultySettings {
+                enemy: EnemyStatScale {
+                    health: 1.3,
+                    damage: 1.25,
+                    attack_speed: 1.15,
+                },
+                wave_delay: 0.8,
+                starting_bloodrock: 0.75,
+                unit_cost: 1.2,
+                supply_cap: 0.8,
+            }),
+            DifficultyPreset::Custom => None,
+        }
+    }
+
+    pub fn starting_bloodrock(&self, base: usize) -> usize {
+        scale(base, self.starting_bloodrock)
+    }
+
+    pub fn unit_cost(&self, base: usize) -> usize {
+        scale(base, self.unit_cost).max(1)
+    }
+
+    pub fn supply_cap(&self, base: usize) -> usize {
+        scale(base, self.supply_cap).max(1)
+    }
+
+    /// Hand edited files could contain anything
+    fn clamped(mut self) -> Self {
+        for value in [
+            &mut self.enemy.health,
+            &mut self.enemy.damage,
+            &mut self.enemy.attack_speed,
+            &mut self.wave_delay,
+            &mut self.starting_bloodrock,
+            &mut self.unit_cost,
+            &mut self.supply_cap,
+        ] {
+            *value = if value.is_finite() {
+                value.clamp(*MULTIPLIER_RANGE.start(), *MULTIPLIER_RANGE.end())
+            } else {
+                1.
+            };
+        }
+        self
+    }
+}
+
+fn scale(base: usize, multiplier: f32) -> usize {
+    (base as f32 * multiplier).round() as usize
+}
+
+/// Difficulty selected in the main menu
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    /// Used by the `Custom` preset
+    pub custom: DifficultySettings,
+    /// Settings of the replay being played back, they win over the selection
+    #[serde(skip)]
+    pub playback: Option<DifficultySettings>,
+}
+
+impl Difficulty {
+    /// What the game plays with right now, replay files are no more trusted
+    /// than the settings file
+    pub fn settings(&self) -> DifficultySettings {
+        self.playback
+            .map(DifficultySettings::clamped)
+            .unwrap_or_else(|| self.selected())
+    }
+
+    /// What the menu shows
+    pub fn selected(&self) -> DifficultySettings {
+        DifficultySettings::from_preset(self.preset)
+            .unwrap_or(self.custom)
+            .clamped()
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = storage::save(DIFFICULTY_FILE, self) {
+            warn!("Failed to save the difficulty: {}", err);
+        }
+    }
+}
+
+fn load_difficulty(mut difficulty: ResMut<Difficulty>) {
+    match storage::load::<Difficulty>(DIFFICULTY_FILE) {
+        Ok(Some(loaded)) => *difficulty = loaded,
+        Ok(None) => {}
+        Err(err) => warn!("Failed to load the difficulty: {}", err),
+    }
+}
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Difficulty>()
+            .add_startup_system(load_difficulty);
+    }
+}
@@ -0,0 +1,752 @@
+//! Level editor, only built with the `debug_gui` feature
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    elite::EliteDescriptor,
+    enemy_archetype::EnemyArchetypes,
+    enemy_logic::{EnemyId, LevelManager},
+    game::{
+        handle_keyboard_movement, spawn_background, DontSortZ, GameAssets,
+        ResourceAssets,
+    },
+    interaction::window_to_world,
+    level::{
+        default_decorations, parse_level, ArenaDescriptor,
+        BackgroundDescriptor, DecorationDescriptor, LevelAsset,
+        SpawnGroupDescriptor, WaveDescriptor, WaveTrigger,
+    },
+    objectives::{ObjectiveDescriptor, ObjectiveGoal},
+    spawn_formation::{ArenaEdge, Formation},
+    GameMode, PlayerCamera, SceneState,
+};
+
+/// Exported levels end up next to the campaign levels
+const LEVELS_DIR: &str = "assets/levels";
+const CAMERA_SPEED: f32 = 800.;
+/// Right click removes the closest object in this radius
+const PICK_RADIUS: f32 = 80.;
+const ARENA_OUTLINE_WIDTH: f32 = 10.;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EditorTool {
+    SpawnGroup,
+    BloodrockNode,
+    Forest,
+}
+
+pub struct LevelEditor {
+    level: LevelAsset,
+    selected_wave: usize,
+    tool: EditorTool,
+    /// Enemy type of new spawn groups, and of the "+" button of a group
+    enemy_to_add: EnemyId,
+    /// File name inside [`LEVELS_DIR`]
+    file_name: String,
+    status: String,
+    /// What the gizmos were last built from
+    gizmo_snapshot: Option<(LevelAsset, usize)>,
+}
+
+impl Default for LevelEditor {
+    fn default() -> Self {
+        LevelEditor {
+            level: LevelAsset {
+                name: "New level".to_string(),
+                waves: vec![WaveDescriptor {
+                    spawn_data: Vec::new(),
+                    time_to_spawn_after_last_wave: 5.,
+                    trigger: WaveTrigger::Delay,
+                    early_call_bonus: None,
+                    elites: EliteDescriptor::default(),
+                }],
+                bloodrock_nodes: Vec::new(),
+                arena: ArenaDescriptor::default(),
+                background: BackgroundDescriptor::default(),
+                decorations: None,
+                objectives: vec![ObjectiveDescriptor {
+                    goal: ObjectiveGoal::KillAllWaves,
+                    secondary: false,
+                }],
+            },
+            selected_wave: 0,
+            tool: EditorTool::SpawnGroup,
+            enemy_to_add: EnemyId::from("Thrash"),
+            file_name: "custom.level.json".to_string(),
+            status: String::new(),
+            gizmo_snapshot: None,
+        }
+    }
+}
+
+impl LevelEditor {
+    /// Runs the level through the same checks as the asset loader
+    fn validate(&self) -> Result<(), String> {
+        let json =
+            serde_json::to_vec(&self.level).map_err(|err| err.to_string())?;
+        parse_level(&json)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    fn path(&self) -> std::path::PathBuf {
+        let mut file_name = self.file_name.clone();
+        if !file_name.ends_with(".level.json") {
+            file_name.push_str(".level.json");
+        }
+        std::path::Path::new(LEVELS_DIR).join(file_name)
+    }
+
+    fn export(&self) -> Result<String, String> {
+        self.validate()?;
+        let json = serde_json::to_string_pretty(&self.level)
+            .map_err(|err| err.to_string())?;
+        let path = self.path();
+        std::fs::create_dir_all(LEVELS_DIR).map_err(|err| err.to_string())?;
+        std::fs::write(&path, json).map_err(|err| err.to_string())?;
+        Ok(format!("Exported to {}", path.display()))
+    }
+
+    fn open(&mut self) -> Result<String, String> {
+        let path = self.path();
+        let bytes = std::fs::read(&path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.level = parse_level(&bytes)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.selected_wave = 0;
+        Ok(format!("Opened {}", path.display()))
+    }
+}
+
+#[derive(Component)]
+struct EditorGizmo;
+
+/// Same flips every time, so the preview matches what gets exported
+fn default_forests() -> Vec<DecorationDescriptor> {
+    default_decorations(&mut StdRng::seed_from_u64(0))
+}
+
+fn arena_ui(ui: &mut egui::Ui, level: &mut LevelAsset) {
+    egui::CollapsingHeader::new("Arena").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Min");
+            ui.add(egui::DragValue::new(&mut level.arena.min[0]));
+            ui.add(egui::DragValue::new(&mut level.arena.min[1]));
+            ui.label("Max");
+            ui.add(egui::DragValue::new(&mut level.arena.max[0]));
+            ui.add(egui::DragValue::new(&mut level.arena.max[1]));
+        });
+        let background = &mut level.background;
+        ui.horizontal(|ui| {
+            ui.label("Background tiles");
+            ui.add(egui::DragValue::new(&mut background.min_tile[0]));
+            ui.add(egui::DragValue::new(&mut background.min_tile[1]));
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut background.max_tile[0]));
+            ui.add(egui::DragValue::new(&mut background.max_tile[1]));
+            ui.label("scale");
+            ui.add(
+                egui::DragValue::new(&mut background.scale)
+                    .speed(0.05)
+                    .clamp_range(0.1..=10.0),
+            );
+        });
+        if ui.button("Reset forests to the default").clicked() {
+            level.decorations = None;
+        }
+    });
+}
+
+fn formation_ui(
+    ui: &mut egui::Ui,
+    group_index: usize,
+    group: &mut SpawnGroupDescriptor,
+) {
+    let formations = [
+        ("Scattered", Formation::default()),
+        ("Line", Formation::Line { spacing: 100. }),
+        ("Wedge", Formation::Wedge { spacing: 100. }),
+        ("Ring", Formation::Ring { radius: 150. }),
+        ("Column", Formation::Column { spacing: 100. }),
+    ];
+    let selected = formations
+        .iter()
+        .position(|(_, f)| {
+            std::mem::discriminant(f)
+                == std::mem::discriminant(&group.formation)
+        })
+        .unwrap_or_default();
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source(("formation", group_index))
+            .selected_text(formations[selected].0)
+            .show_ui(ui, |ui| {
+                for (index, (label, default)) in formations.iter().enumerate() {
+                    if ui.selectable_label(index == selected, *label).clicked()
+                        && index != selected
+                    {
+                        group.formation = *default;
+                    }
+                }
+            });
+        match &mut group.formation {
+            Formation::Scattered { radius } | Formation::Ring { radius } => {
+                ui.add(egui::DragValue::new(radius).prefix("radius "));
+            }
+            Formation::Line { spacing }
+            | Formation::Wedge { spacing }
+            | Formation::Column { spacing } => {
+                ui.add(egui::DragValue::new(spacing).prefix("spacing "));
+            }
+        }
+        egui::ComboBox::from_id_source(("entry", group_index))
+            .selected_text(match group.entry {
+                Some(edge) => format!("From {:?}", edge),
+                None => "Spawn in place".to_string(),
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut group.entry, None, "Spawn in place");
+                for edge in [
+                    ArenaEdge::North,
+                    ArenaEdge::South,
+                    ArenaEdge::East,
+                    ArenaEdge::West,
+                ] {
+                    ui.selectable_value(
+                        &mut group.entry,
+                        Some(edge),
+                        format!("From {:?}", edge),
+                    );
+                }
+            });
+    });
+}
+
+fn trigger_ui(ui: &mut egui::Ui, trigger: &mut WaveTrigger) {
+    let triggers = [
+        ("After the delay", WaveTrigger::Delay),
+        ("Few enemies left", WaveTrigger::EnemiesBelow { count: 3 }),
+        ("All enemies dead", WaveTrigger::AllEnemiesDead),
+        (
+            "Player in region",
+            WaveTrigger::PlayerInRegion {
+                min: [-200., -200.],
+                max: [200., 200.],
+            },
+        ),
+    ];
+    let selected = triggers
+        .iter()
+        .position(|(_, t)| {
+            std::mem::discriminant(t) == std::mem::discriminant(trigger)
+        })
+        .unwrap_or_default();
+    egui::ComboBox::from_label("Trigger")
+        .selected_text(triggers[selected].0)
+        .show_ui(ui, |ui| {
+            for (index, (label, default)) in triggers.iter().enumerate() {
+                if ui.selectable_label(index == selected, *label).clicked()
+                    && index != selected
+                {
+                    *trigger = *default;
+                }
+            }
+        });
+    match trigger {
+        WaveTrigger::EnemiesBelow { count } => {
+            ui.horizontal(|ui| {
+                ui.label("Fewer enemies than");
+                ui.add(egui::DragValue::new(count));
+            });
+        }
+        WaveTrigger::PlayerInRegion { min, max } => {
+            ui.horizontal(|ui| {
+                ui.label("Min");
+                ui.add(egui::DragValue::new(&mut min[0]));
+                ui.add(egui::DragValue::new(&mut min[1]));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(&mut max[0]));
+                ui.add(egui::DragValue::new(&mut max[1]));
+            });
+        }
+        WaveTrigger::Delay | WaveTrigger::AllEnemiesDead => {}
+    }
+}
+
+fn editor_ui_system(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut editor: ResMut<LevelEditor>,
+    mut levels: ResMut<Assets<LevelAsset>>,
+    mut level_manager: ResMut<LevelManager>,
+    mut game_mode: ResMut<GameMode>,
+    mut app_state: ResMut<State<SceneState>>,
+    archetypes: Res<EnemyArchetypes>,
+) {
+    let editor = &mut *editor;
+    egui::Window::new("Level editor")
+        .id(egui::Id::new(6))
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_TOP, egui::Vec2 { x: 10., y: 10. })
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut editor.level.name);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Left click places");
+                ui.selectable_value(
+                    &mut editor.tool,
+                    EditorTool::SpawnGroup,
+                    "Spawn group",
+                );
+                ui.selectable_value(
+                    &mut editor.tool,
+                    EditorTool::BloodrockNode,
+                    "Bloodrock node",
+                );
+                ui.selectable_value(
+                    &mut editor.tool,
+                    EditorTool::Forest,
+                    "Forest",
+                );
+            });
+            ui.label("Right click removes, WASD moves the camera");
+            egui::ComboBox::from_label("Enemy")
+                .selected_text(editor.enemy_to_add.to_string())
+                .show_ui(ui, |ui| {
+                    for enemy in archetypes.ids() {
+                        let label = enemy.to_string();
+                        ui.selectable_value(
+                            &mut editor.enemy_to_add,
+                            enemy,
+                            label,
+                        );
+                    }
+                });
+            arena_ui(ui, &mut editor.level);
+            ui.separator();
+
+            ui.horizontal_wrapped(|ui| {
+                for index in 0..editor.level.waves.len() {
+                    ui.selectable_value(
+                        &mut editor.selected_wave,
+                        index,
+                        format!("Wave {}", index + 1),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Add wave").clicked() {
+                    editor.level.waves.push(WaveDescriptor {
+                        spawn_data: Vec::new(),
+                        time_to_spawn_after_last_wave: 10.,
+                        trigger: WaveTrigger::Delay,
+                        early_call_bonus: None,
+                        elites: EliteDescriptor::default(),
+                    });
+                    editor.selected_wave = editor.level.waves.len() - 1;
+                }
+                if ui.button("Remove wave").clicked()
+                    && editor.level.waves.len() > 1
+                {
+                    editor.level.waves.remove(editor.selected_wave);
+                    editor.selected_wave =
+                        editor.selected_wave.min(editor.level.waves.len() - 1);
+                }
+            });
+
+            let enemy_to_add = editor.enemy_to_add.clone();
+            if let Some(wave) = editor.level.waves.get_mut(editor.selected_wave)
+            {
+                ui.horizontal(|ui| {
+                    ui.label("Delay after the last wave");
+                    ui.add(
+                        egui::DragValue::new(
+                            &mut wave.time_to_spawn_after_last_wave,
+                        )
+                        .speed(0.5)
+                        .clamp_range(0.0..=600.0)
+                        .suffix(" s"),
+                    );
+                });
+                trigger_ui(ui, &mut wave.trigger);
+                ui.horizontal(|ui| {
+                    let mut can_call_early = wave.early_call_bonus.is_some();
+                    ui.checkbox(&mut can_call_early, "Call early bonus");
+                    if !can_call_early {
+                        wave.early_call_bonus = None;
+                    } else if wave.early_call_bonus.is_none() {
+                        wave.early_call_bonus = Some(5);
+                    }
+                    if let Some(bonus) = &mut wave.early_call_bonus {
+                        ui.add(egui::DragValue::new(bonus));
+                    }
+                });
+
+                let mut remove_group = None;
+                egui::ScrollArea::vertical()
+                    .max_height(300.)
+                    .show(ui, |ui| {
+                        for (group_index, group) in
+                            wave.spawn_data.iter_mut().enumerate()
+                        {
+                            ui.label(format!(
+                                "Group {} at ({:.0}, {:.0})",
+                                group_index + 1,
+                                group.position[0],
+                                group.position[1]
+                            ));
+                            ui.horizontal_wrapped(|ui| {
+                                let mut remove_enemy = None;
+                                for (enemy_index, enemy) in
+                                    group.enemies.iter().enumerate()
+                                {
+                                    if ui
+                                        .button(format!("{} x", enemy))
+                                        .clicked()
+                                    {
+                                        remove_enemy = Some(enemy_index);
+                                    }
+                                }
+                                if let Some(index) = remove_enemy {
+                                    group.enemies.remove(index);
+                                }
+                                if ui.button("+").clicked() {
+                                    group.enemies.push(enemy_to_add.clone());
+                                }
+                                if ui.button("Remove group").clicked() {
+                                    remove_group = Some(group_index);
+                                }
+                            });
+                            formation_ui(ui, group_index, group);
+                        }
+                    });
+                if let Some(index) = remove_group {
+                    wave.spawn_data.remove(index);
+                }
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut editor.file_name);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Open").clicked() {
+                    editor.status = match editor.open() {
+                        Ok(status) | Err(status) => status,
+                    };
+                }
+                if ui.button("Export").clicked() {
+                    editor.status = match editor.export() {
+                        Ok(status) | Err(status) => status,
+                    };
+                }
+                if ui.button("Play-test").clicked() {
+                    match editor.validate() {
+                        Ok(()) => {
+                            level_manager.level_handle =
+                                levels.add(editor.level.clone());
+                            *game_mode = GameMode::Campaign;
+                            app_state
+                                .set(SceneState::LoadingLevel)
+                                .unwrap_or_default();
+                        }
+                        Err(err) => editor.status = err,
+                    }
+                }
+                if ui.button("Back to Menu").clicked() {
+                    app_state.set(SceneState::MainMenu).unwrap_or_default();
+                }
+            });
+            if !editor.status.is_empty() {
+                ui.label(&editor.status);
+            }
+        });
+}
+
+fn editor_place_system(
+    mut editor: ResMut<LevelEditor>,
+    mut egui_ctx: ResMut<EguiContext>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    cameras: Query<
+        (&GlobalTransform, &OrthographicProjection),
+        With<PlayerCamera>,
+    >,
+) {
+    let place = buttons.just_pressed(MouseButton::Left);
+    let remove = buttons.just_pressed(MouseButton::Right);
+    if !place && !remove || egui_ctx.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let (cam_tr, proj) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let pos = window_to_world(cursor, window, *cam_tr, proj).truncate();
+
+    let editor = &mut *editor;
+    if place {
+        let position = [pos.x, pos.y];
+        match editor.tool {
+            EditorTool::SpawnGroup => {
+                let enemy = editor.enemy_to_add.clone();
+                if let Some(wave) =
+                    editor.level.waves.get_mut(editor.selected_wave)
+                {
+                    wave.spawn_data.push(SpawnGroupDescriptor {
+                        enemies: vec![enemy],
+                        position,
+                        formation: Formation::default(),
+                        entry: None,
+                    });
+                }
+            }
+            EditorTool::BloodrockNode => {
+                editor.level.bloodrock_nodes.push(position);
+            }
+            EditorTool::Forest => {
+                editor
+                    .level
+                    .decorations
+                    .get_or_insert_with(default_forests)
+                    .push(DecorationDescriptor {
+                        position,
+                        flip_x: false,
+                    });
+            }
+        }
+        return;
+    }
+
+    enum Picked {
+        SpawnGroup(usize),
+        BloodrockNode(usize),
+        Forest(usize),
+    }
+    let mut closest: Option<(f32, Picked)> = None;
+    let mut consider = |position: [f32; 2], picked: Picked| {
+        let distance = Vec2::from(position).distance(pos);
+        if distance < PICK_RADIUS
+            && closest.as_ref().map_or(true, |(d, _)| distance < *d)
+        {
+            closest = Some((distance, picked));
+        }
+    };
+    if let Some(wave) = editor.level.waves.get(editor.selected_wave) {
+        for (i, group) in wave.spawn_data.iter().enumerate() {
+            consider(group.position, Picked::SpawnGroup(i));
+        }
+    }
+    for (i, node) in editor.level.bloodrock_nodes.iter().enumerate() {
+        consider(*node, Picked::BloodrockNode(i));
+    }
+    // the default forests only become part of the level once one is removed
+    let mut decorations = editor
+        .level
+        .decorations
+        .clone()
+        .unwrap_or_else(default_forests);
+    for (i, decoration) in decorations.iter().enumerate() {
+        consider(decoration.position, Picked::Forest(i));
+    }
+
+    match closest {
+        Some((_, Picked::SpawnGroup(i))) => {
+            editor.level.waves[editor.selected_wave]
+                .spawn_data
+                .remove(i);
+        }
+        Some((_, Picked::BloodrockNode(i))) => {
+            editor.level.bloodrock_nodes.remove(i);
+        }
+        Some((_, Picked::Forest(i))) => {
+            decorations.remove(i);
+            editor.level.decorations = Some(decorations);
+        }
+        None => {}
+    }
+}
+
+fn editor_camera_system(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut cameras: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let mut delta = Vec2::ZERO;
+    handle_keyboard_movement(
+        &mut delta,
+        &mut false,
+        &mut false,
+        &keyboard_input,
+    );
+    for mut tr in cameras.iter_mut() {
+        tr.translation +=
+            delta.extend(0.) * CAMERA_SPEED * time.delta_seconds();
+    }
+}
+
+/// Spawn groups of the selected wave are red, the other waves are faded
+fn editor_gizmo_system(
+    mut cmd: Commands,
+    mut editor: ResMut<LevelEditor>,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    gizmos: Query<Entity, With<EditorGizmo>>,
+) {
+    let editor = &mut *editor;
+    if let Some((level, selected_wave)) = &editor.gizmo_snapshot {
+        if *level == editor.level && *selected_wave == editor.selected_wave {
+            return;
+        }
+    }
+    editor.gizmo_snapshot = Some((editor.level.clone(), editor.selected_wave));
+
+    for entity in gizmos.iter() {
+        cmd.entity(entity).despawn_recursive();
+    }
+    for tile in
+        spawn_background(&mut cmd, &game_assets, &editor.level.background)
+    {
+        cmd.entity(tile).insert(EditorGizmo);
+    }
+    let arena = &editor.level.arena;
+    let (min, max) = (Vec2::from(arena.min), Vec2::from(arena.max));
+    let size = max - min;
+    let center = (min + max) / 2.;
+    for (position, bar_size) in [
+        (
+            Vec2::new(center.x, min.y),
+            Vec2::new(size.x, ARENA_OUTLINE_WIDTH),
+        ),
+        (
+            Vec2::new(center.x, max.y),
+            Vec2::new(size.x, ARENA_OUTLINE_WIDTH),
+        ),
+        (
+            Vec2::new(min.x, center.y),
+            Vec2::new(ARENA_OUTLINE_WIDTH, size.y),
+        ),
+        (
+            Vec2::new(max.x, center.y),
+            Vec2::new(ARENA_OUTLINE_WIDTH, size.y),
+        ),
+    ] {
+        cmd.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1., 1., 0., 0.6),
+                custom_size: Some(bar_size),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.extend(1.)),
+            ..Default::default()
+        })
+        .insert(DontSortZ)
+        .insert(EditorGizmo);
+    }
+    for (index, wave) in editor.level.waves.iter().enumerate() {
+        let color = if index == editor.selected_wave {
+            Color::RED
+        } else {
+            Color::rgba(1., 1., 1., 0.3)
+        };
+        for group in wave.spawn_data.iter() {
+            let mut tr = Transform::from_translation(
+                Vec2::from(group.position).extend(1.),
+            );
+            tr.scale = Vec3::splat(1. + group.enemies.len() as f32 * 0.25);
+            cmd.spawn_bundle(SpriteSheetBundle {
+                texture_atlas: game_assets.circle_sprite.clone(),
+                sprite: TextureAtlasSprite {
+                    color,
+                    ..Default::default()
+                },
+                transform: tr,
+                ..Default::default()
+            })
+            .insert(DontSortZ)
+            .insert(EditorGizmo);
+        }
+    }
+    for node in editor.level.bloodrock_nodes.iter() {
+        cmd.spawn_bundle(SpriteSheetBundle {
+            texture_atlas: resource_assets.bloodrock_node.clone(),
+            transform: Transform::from_translation(
+                Vec2::from(*node).extend(0.5),
+            ),
+            ..Default::default()
+        })
+        .insert(DontSortZ)
+        .insert(EditorGizmo);
+    }
+    let preview;
+    let decorations = match &editor.level.decorations {
+        Some(decorations) => decorations,
+        None => {
+            preview = default_forests();
+            &preview
+        }
+    };
+    for decoration in decorations.iter() {
+        let mut tr = Transform::from_translation(
+            Vec2::from(decoration.position).extend(0.5),
+        );
+        tr.scale = Vec3::splat(2.2);
+        cmd.spawn_bundle(SpriteSheetBundle {
+            texture_atlas: game_assets.forests.clone(),
+            sprite: TextureAtlasSprite {
+                flip_x: decoration.flip_x,
+                ..Default::default()
+            },
+            transform: tr,
+            ..Default::default()
+        })
+        .insert(DontSortZ)
+        .insert(EditorGizmo);
+    }
+}
+
+fn exit_editor_system(
+    mut cmd: Commands,
+    mut editor: ResMut<LevelEditor>,
+    gizmos: Query<Entity, With<EditorGizmo>>,
+) {
+    for entity in gizmos.iter() {
+        cmd.entity(entity).despawn_recursive();
+    }
+    editor.gizmo_snapshot = None;
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelEditor>()
+            .add_system_set(
+                SystemSet::on_update(SceneState::Editor)
+                    .with_system(editor_ui_system)
+                    .with_system(editor_place_system.after(editor_ui_system))
+                    .with_system(editor_camera_system)
+                    .with_system(
+                        editor_gizmo_system.after(editor_place_system),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(SceneState::Editor)
+                    .with_system(exit_editor_system),
+            );
+    }
+}
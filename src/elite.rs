@@ -0,0 +1,309 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy_archetype::EnemyArchetypes,
+    enemy_logic::{spawn_enemy, BasicEnemyLogic, EnemyKind, EnemyStatScale},
+    game::{DontSortZ, GameAssets, ResourceAssets, Velocity},
+    health::{
+        hp_material, spawn_health_particles, DestroyEntity, Health,
+        HealthChangedEvent,
+    },
+    GameTime, SceneState,
+};
+
+/// Extra bloodrock for killing an elite, per affix
+pub const BLOODROCK_PER_AFFIX: usize = 2;
+
+const ELITE_HEALTH: f32 = 1.5;
+const ELITE_TINT: Color = Color::rgb(1., 0.8, 0.3);
+const FAST_SPEED: f32 = 1.5;
+/// Fraction of the damage dealt that heals a vampiric elite
+const VAMPIRIC_LEECH: f32 = 0.4;
+const EXPLOSION_RADIUS: f32 = 200.;
+const EXPLOSION_DAMAGE: f32 = 2.;
+const SHIELD_ARMOR: f32 = 0.4;
+/// Fraction of the max health regenerated per second
+const REGENERATION: f32 = 0.03;
+/// Enemies a splitting elite splits into
+const SPLITS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Affix {
+    Fast,
+    Vampiric,
+    /// Damages everything around it on death
+    Explosive,
+    Shielded,
+    Regenerating,
+    /// Splits into smaller copies on death
+    Splitting,
+}
+
+impl Affix {
+    pub const ALL: [Affix; 6] = [
+        Affix::Fast,
+        Affix::Vampiric,
+        Affix::Explosive,
+        Affix::Shielded,
+        Affix::Regenerating,
+        Affix::Splitting,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Affix::Fast => "Fast",
+            Affix::Vampiric => "Vampiric",
+            Affix::Explosive => "Explosive",
+            Affix::Shielded => "Shielded",
+            Affix::Regenerating => "Regenerating",
+            Affix::Splitting => "Splitting",
+        }
+    }
+}
+
+/// How likely spawned enemies are elites, and what they can roll
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EliteDescriptor {
+    /// Between 0 and 1
+    pub chance: f32,
+    pub max_affixes: usize,
+    /// Affixes to pick from, any if empty
+    pub affixes: Vec<Affix>,
+}
+
+impl Default for EliteDescriptor {
+    fn default() -> Self {
+        EliteDescriptor {
+            chance: 0.,
+            max_affixes: 1,
+            affixes: Vec::new(),
+        }
+    }
+}
+
+impl EliteDescriptor {
+    /// Empty for regular enemies, never touches `rng` if elites are off
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<Affix> {
+        if self.chance <= 0. || rng.gen::<f32>() >= self.chance {
+            return Vec::new();
+        }
+        let pool: &[Affix] = if self.affixes.is_empty() {
+            &Affix::ALL
+        } else {
+            &self.affixes
+        };
+        let count = rng.gen_range(1..=self.max_affixes.clamp(1, pool.len()));
+        pool.choose_multiple(rng, count).copied().collect()
+    }
+}
+
+#[derive(Component)]
+pub struct Elite {
+    pub affixes: Vec<Affix>,
+    /// Restored from a save, its saved health already has the elite bonus
+    health_applied: bool,
+}
+
+impl Elite {
+    pub fn new(affixes: Vec<Affix>) -> Self {
+        Elite {
+            affixes,
+            health_applied: false,
+        }
+    }
+
+    pub fn restored(affixes: Vec<Affix>) -> Self {
+        Elite {
+            affixes,
+            health_applied: true,
+        }
+    }
+
+    pub fn has(&self, affix: Affix) -> bool {
+        self.affixes.contains(&affix)
+    }
+}
+
+/// Applies the affixes of fresh elites and gives them their nameplate
+fn setup_elites_system(
+    mut cmd: Commands,
+    game_assets: Res<GameAssets>,
+    mut elites: Query<
+        (
+            Entity,
+            &Elite,
+            &mut Velocity,
+            &mut Health,
+            &mut TextureAtlasSprite,
+        ),
+        Added<Elite>,
+    >,
+) {
+    for (entity, elite, mut vel, mut health, mut sprite) in elites.iter_mut() {
+        if !elite.health_applied {
+            health.max_health *= ELITE_HEALTH;
+            health.current_health *= ELITE_HEALTH;
+            if elite.has(Affix::Shielded) {
+                health.armor = (health.armor + SHIELD_ARMOR).min(0.9);
+            }
+        }
+        if elite.has(Affix::Fast) {
+            vel.0 *= FAST_SPEED;
+        }
+        sprite.color = ELITE_TINT;
+
+        let name = elite
+            .affixes
+            .iter()
+            .map(|affix| affix.name())
+            .collect::<Vec<_>>()
+            .join(" ");
+        cmd.entity(entity).with_children(|cmd| {
+            cmd.spawn_bundle(Text2dBundle {
+                text: Text::from_section(
+                    name,
+                    TextStyle {
+                        font: game_assets.font.clone(),
+                        font_size: 22.,
+                        color: ELITE_TINT,
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_translation(
+                    Vec3::Z * 200.0 + Vec3::Y * 90.0,
+                ),
+                ..Default::default()
+            })
+            .insert(DontSortZ);
+        });
+    }
+}
+
+fn regenerating_system(
+    time: Res<GameTime>,
+    mut elites: Query<(&Elite, &mut Health)>,
+) {
+    for (elite, mut health) in elites.iter_mut() {
+        if elite.has(Affix::Regenerating) && health.current_health > 0. {
+            health.current_health = (health.current_health
+                + health.max_health * REGENERATION * time.delta_seconds())
+            .min(health.max_health);
+        }
+    }
+}
+
+fn vampiric_system(
+    mut health_changed_events: EventReader<HealthChangedEvent>,
+    mut elites: Query<(&Elite, &mut Health)>,
+) {
+    for event in health_changed_events.iter() {
+        if event.amount >= 0. {
+            continue;
+        }
+        let source = match event.source {
+            Some(source) => source,
+            None => continue,
+        };
+        if let Ok((elite, mut health)) = elites.get_mut(source) {
+            if elite.has(Affix::Vampiric) && health.current_health > 0. {
+                health.current_health = (health.current_health
+                    - event.amount * VAMPIRIC_LEECH)
+                    .min(health.max_health);
+            }
+        }
+    }
+}
+
+/// Runs before the dead get despawned at the end of the frame
+fn elite_death_system(
+    mut cmd: Commands,
+    mut destroy_events: EventReader<DestroyEntity>,
+    elites: Query<(&Elite, &Health, &GlobalTransform, &EnemyKind)>,
+    allies: Query<
+        (Entity, &GlobalTransform),
+        (With<Health>, Without<BasicEnemyLogic>),
+    >,
+    archetypes: Res<EnemyArchetypes>,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut send_health_changed_event: EventWriter<HealthChangedEvent>,
+) {
+    for event in destroy_events.iter() {
+        let (elite, health, tr, kind) = match elites.get(event.0) {
+            Ok(elite) => elite,
+            Err(_) => continue,
+        };
+        // escaped raiders do not explode
+        if health.current_health > 0. {
+            continue;
+        }
+        let pos = tr.translation();
+        if elite.has(Affix::Explosive) {
+            spawn_health_particles(
+                &mut cmd,
+                game_assets.circle_sprite.clone(),
+                pos,
+            );
+            for (ally, ally_tr) in allies.iter() {
+                if (ally_tr.translation() - pos).truncate().length()
+                    <= EXPLOSION_RADIUS
+                {
+                    send_health_changed_event.send(HealthChangedEvent {
+                        target: ally,
+                        amount: -EXPLOSION_DAMAGE,
+                        piercing: 0.,
+                        source: None,
+                    });
+                }
+            }
+        }
+        if elite.has(Affix::Splitting) {
+            // the splits are regular enemies, or they would split forever
+            let scale = kind.stat_scale
+                * EnemyStatScale {
+                    health: 0.5,
+                    damage: 0.6,
+                    attack_speed: 1.,
+                };
+            for index in 0..SPLITS {
+                let offset = index as f32 - (SPLITS - 1) as f32 / 2.;
+                let split_pos = pos + Vec3::X * offset * 60.;
+                if let Some(split) = spawn_enemy(
+                    &kind.kind,
+                    &mut cmd,
+                    &archetypes,
+                    split_pos,
+                    &mut *hp_assets,
+                    &mut *mesh_assets,
+                    &resource_assets,
+                    &scale,
+                ) {
+                    cmd.entity(split).insert(
+                        Transform::from_translation(split_pos)
+                            .with_scale(Vec3::splat(0.7)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub struct ElitePlugin;
+
+impl Plugin for ElitePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(setup_elites_system)
+                .with_system(regenerating_system)
+                .with_system(vampiric_system),
+        )
+        // the destroyed entities are still around in `PostUpdate`
+        .add_system_to_stage(CoreStage::PostUpdate, elite_death_system);
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    difficulty::{Difficulty, DifficultySettings},
+    elite::EliteDescriptor,
+    enemy_logic::{EnemyId, EnemySpawner, EnemyStatScale},
+    storage,
+    ui::{EndGameManager, EndGameState},
+    GameMode, GameTime, SceneState,
+};
+
+const RECORD_FILE: &str = "endless.json";
+
+/// Spawners sit outside of the arena, enemies walk in from there
+const SPAWNER_POSITIONS: [Vec3; 4] = [
+    Vec3::new(0., 1300., 0.),
+    Vec3::new(0., -1300., 0.),
+    Vec3::new(1300., 0., 0.),
+    Vec3::new(-1300., 0., 0.),
+];
+
+/// State of the current endless run
+#[derive(Default)]
+pub struct EndlessRun {
+    /// Survived time in seconds
+    pub elapsed: f32,
+    pub finished: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct EndlessRecord {
+    /// Best survival time in seconds
+    pub best_time: f32,
+}
+
+pub fn format_survival_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn enemy_pool(elapsed: f32) -> Vec<EnemyId> {
+    let mut pool = vec![
+        EnemyId::from("Thrash"),
+        EnemyId::from("Thrash"),
+        EnemyId::from("Ranged"),
+        EnemyId::from("Sworder"),
+    ];
+    if elapsed > 60. {
+        pool.push(EnemyId::from("Piker"));
+        pool.push(EnemyId::from("Sworder"));
+    }
+    if elapsed > 120. {
+        pool.push(EnemyId::from("Armored"));
+        pool.push(EnemyId::from("Healer"));
+        pool.push(EnemyId::from("Ranged"));
+    }
+    if elapsed > 300. {
+        pool.push(EnemyId::from("Boss1"));
+    }
+    if elapsed > 420. {
+        pool.push(EnemyId::from("Boss2"));
+    }
+    pool
+}
+
+/// Seconds between two spawns of a single spawner
+fn spawn_interval(elapsed: f32, difficulty: &DifficultySettings) -> f32 {
+    (8. - elapsed / 45.).max(2.) * difficulty.wave_delay
+}
+
+fn stat_scale(elapsed: f32, difficulty: &DifficultySettings) -> EnemyStatScale {
+    EnemyStatScale {
+        health: 1. + elapsed / 150.,
+        damage: 1. + elapsed / 300.,
+        attack_speed: 1. + elapsed / 600.,
+    } * difficulty.enemy
+}
+
+/// No elites in the first minute, then more and stronger ones
+fn elites(elapsed: f32) -> EliteDescriptor {
+    EliteDescriptor {
+        chance: ((elapsed - 60.) / 600.).clamp(0., 0.5),
+        max_affixes: 1 + (elapsed / 240.) as usize,
+        affixes: Vec::new(),
+    }
+}
+
+fn setup_endless_run(
+    mut cmd: Commands,
+    game_mode: Res<GameMode>,
+    mut run: ResMut<EndlessRun>,
+    difficulty: Res<Difficulty>,
+    spawners: Query<Entity, With<EnemySpawner>>,
+) {
+    if *game_mode != GameMode::Endless || !spawners.is_empty() {
+        return;
+    }
+    *run = EndlessRun::default();
+    let difficulty = difficulty.settings();
+    for pos in SPAWNER_POSITIONS {
+        cmd.spawn()
+            .insert(EnemySpawner {
+                time_between_spawns: Timer::from_seconds(
+                    spawn_interval(0., &difficulty),
+                    false,
+                ),
+                distance_from_spawn_point: 200.,
+                pool: enemy_pool(0.),
+                stat_scale: stat_scale(0., &difficulty),
+                elites: elites(0.),
+            })
+            .insert(Transform::from_translation(pos))
+            .insert(GlobalTransform::default());
+    }
+}
+
+fn endless_ramp_system(
+    time: Res<GameTime>,
+    game_mode: Res<GameMode>,
+    end_game_manager: Res<EndGameManager>,
+    mut run: ResMut<EndlessRun>,
+    mut record: ResMut<EndlessRecord>,
+    difficulty: Res<Difficulty>,
+    mut spawners: Query<&mut EnemySpawner>,
+) {
+    if *game_mode != GameMode::Endless || run.finished {
+        return;
+    }
+    if matches!(end_game_manager.state, EndGameState::Lose) {
+        finish_run(&mut run, &mut record);
+        return;
+    }
+
+    run.elapsed += time.delta_seconds();
+    let difficulty = difficulty.settings();
+    let interval =
+        Duration::from_secs_f32(spawn_interval(run.elapsed, &difficulty));
+    for mut spawner in spawners.iter_mut() {
+        if spawner.time_between_spawns.duration() != interval {
+            spawner.time_between_spawns.set_duration(interval);
+        }
+        spawner.pool = enemy_pool(run.elapsed);
+        spawner.stat_scale = stat_scale(run.elapsed, &difficulty);
+        spawner.elites = elites(run.elapsed);
+    }
+}
+
+/// Ends the run, keeping its time if it beat the record
+fn finish_run(run: &mut EndlessRun, record: &mut EndlessRecord) {
+    run.finished = true;
+    if run.elapsed > record.best_time {
+        record.best_time = run.elapsed;
+        if let Err(err) = storage::save(RECORD_FILE, record) {
+            warn!("Failed to save endless record: {}", err);
+        }
+    }
+}
+
+/// Runs that are quit before losing still count for the record
+fn leave_endless_run_system(
+    game_mode: Res<GameMode>,
+    mut run: ResMut<EndlessRun>,
+    mut record: ResMut<EndlessRecord>,
+) {
+    if *game_mode == GameMode::Endless && !run.finished {
+        finish_run(&mut run, &mut record);
+    }
+}
+
+fn load_endless_record(mut record: ResMut<EndlessRecord>) {
+    match storage::load::<EndlessRecord>(RECORD_FILE) {
+        Ok(Some(loaded)) => *record = loaded,
+        Ok(None) => {}
+        Err(err) => warn!("Failed to load endless record: {}", err),
+    }
+}
+
+pub struct EndlessPlugin;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EndlessRun::default())
+            .insert_resource(EndlessRecord::default())
+            .add_startup_system(load_endless_record)
+            .add_system_set(
+                SystemSet::on_enter(SceneState::InGame)
+                    .with_system(setup_endless_run),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::InGame)
+                    .with_system(endless_ramp_system),
+            )
+            .add_system_set(
+                SystemSet::on_exit(SceneState::InGame)
+                    .with_system(leave_endless_run_system),
+            )
+            // the pause menu is pushed on top of `InGame`, quitting from there
+            // has to count as well
+            .add_system_set(
+                SystemSet::on_enter(SceneState::MainMenu)
+                    .with_system(leave_endless_run_system),
+            );
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{AttackType, CombatComponent},
+    enemy_logic::BasicEnemyLogic,
+    game::{PlayerController, Velocity},
+    health::Health,
+    spawn_formation::MarchIn,
+    worker_logic::{HealerComponent, UnitFollowPlayer},
+    GameTime, SceneState,
+};
+
+/// How an enemy archetype behaves, everything is off by default so enemies
+/// just walk at their target
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviorDescriptor {
+    /// Backs off from melee units closer than this
+    pub kite_distance: Option<f32>,
+    /// Below this fraction of the max health the enemy walks to a healer
+    pub retreat_health: Option<f32>,
+    /// Retreating enemies go back to fighting above this fraction
+    pub recover_health: f32,
+    /// Below this fraction of the max health the enemy runs away
+    pub flee_health: Option<f32>,
+    /// Seconds, afterwards the enemy fights to the death
+    pub flee_time: f32,
+}
+
+impl Default for BehaviorDescriptor {
+    fn default() -> Self {
+        BehaviorDescriptor {
+            kite_distance: None,
+            retreat_health: None,
+            recover_health: 0.8,
+            flee_health: None,
+            flee_time: 4.,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EnemyState {
+    /// Nothing to attack
+    Idle,
+    Approach,
+    Attack,
+    /// Keeps away from melee units, still shoots when it can
+    Kite,
+    RetreatToHealer,
+    Flee {
+        timer: Timer,
+    },
+}
+
+impl EnemyState {
+    pub fn may_attack(&self) -> bool {
+        matches!(
+            self,
+            EnemyState::Approach | EnemyState::Attack | EnemyState::Kite
+        )
+    }
+
+    pub fn may_approach(&self) -> bool {
+        matches!(self, EnemyState::Approach | EnemyState::Attack)
+    }
+}
+
+#[derive(Component)]
+pub struct EnemyBehavior {
+    pub desc: BehaviorDescriptor,
+    pub state: EnemyState,
+    /// Enemies only flee once
+    fled: bool,
+}
+
+impl EnemyBehavior {
+    pub fn new(desc: BehaviorDescriptor) -> Self {
+        EnemyBehavior {
+            desc,
+            state: EnemyState::Idle,
+            fled: false,
+        }
+    }
+}
+
+fn nearest(pos: Vec2, points: impl Iterator<Item = Vec2>) -> Option<Vec2> {
+    points.min_by(|a, b| {
+        (*a - pos)
+            .length_squared()
+            .total_cmp(&(*b - pos).length_squared())
+    })
+}
+
+/// Picks the state of every enemy and moves the ones that are not fighting,
+/// `combat_system` moves the rest
+fn enemy_behavior_system(
+    time: Res<GameTime>,
+    mut enemies: Query<
+        (
+            Entity,
+            &mut EnemyBehavior,
+            &mut Transform,
+            &GlobalTransform,
+            &Health,
+            &CombatComponent,
+            &Velocity,
+        ),
+        (With<BasicEnemyLogic>, Without<MarchIn>),
+    >,
+    healers: Query<
+        (Entity, &GlobalTransform, &HealerComponent),
+        With<BasicEnemyLogic>,
+    >,
+    threats: Query<
+        (&GlobalTransform, Option<&CombatComponent>),
+        (
+            Or<(With<UnitFollowPlayer>, With<PlayerController>)>,
+            Without<BasicEnemyLogic>,
+        ),
+    >,
+    positions: Query<&GlobalTransform>,
+) {
+    for (entity, mut behavior, mut tr, global_tr, health, combat, vel) in
+        enemies.iter_mut()
+    {
+        let desc = behavior.desc;
+        let pos = global_tr.translation().truncate();
+        let fraction = health.current_health / health.max_health;
+
+        let healer = healers
+            .iter()
+            .filter(|(healer, ..)| *healer != entity)
+            .map(|(_, healer_tr, healer)| {
+                (healer_tr.translation().truncate(), healer.range)
+            })
+            .min_by(|(a, _), (b, _)| {
+                (*a - pos)
+                    .length_squared()
+                    .total_cmp(&(*b - pos).length_squared())
+            });
+        let threat = nearest(
+            pos,
+            threats.iter().map(|(tr, _)| tr.translation().truncate()),
+        );
+        let melee_threat = nearest(
+            pos,
+            threats
+                .iter()
+                .filter(|(_, combat)| {
+                    combat.map_or(false, |c| c.attack_type == AttackType::Melee)
+                })
+                .map(|(tr, _)| tr.translation().truncate()),
+        );
+        let target = combat
+            .target
+            .and_then(|target| positions.get(target).ok())
+            .map(|tr| tr.translation().truncate());
+
+        if let EnemyState::Flee { timer } = &mut behavior.state {
+            timer.tick(time.delta());
+            if timer.finished() {
+                behavior.fled = true;
+            }
+        }
+        let retreating = matches!(behavior.state, EnemyState::RetreatToHealer);
+        let wants_healing = desc.retreat_health.map_or(false, |threshold| {
+            if retreating {
+                fraction < desc.recover_health
+            } else {
+                fraction <= threshold
+            }
+        });
+        let fleeing = matches!(behavior.state, EnemyState::Flee { .. });
+        let wants_to_flee = !behavior.fled
+            && threat.is_some()
+            && desc
+                .flee_health
+                .map_or(false, |threshold| fraction <= threshold);
+
+        let next = if wants_healing && healer.is_some() {
+            EnemyState::RetreatToHealer
+        } else if wants_to_flee {
+            if fleeing {
+                behavior.state.clone()
+            } else {
+                EnemyState::Flee {
+                    timer: Timer::from_seconds(desc.flee_time, false),
+                }
+            }
+        } else if let Some(target) = target {
+            let kiting = desc
+                .kite_distance
+                .zip(melee_threat)
+                .map_or(false, |(distance, threat)| {
+                    (threat - pos).length() < distance
+                });
+            if kiting {
+                EnemyState::Kite
+            } else if (target - pos).length() <= combat.attack_range {
+                EnemyState::Attack
+            } else {
+                EnemyState::Approach
+            }
+        } else {
+            EnemyState::Idle
+        };
+        behavior.state = next;
+
+        let direction = match &behavior.state {
+            EnemyState::RetreatToHealer => healer
+                .filter(|(healer, range)| (*healer - pos).length() > range / 2.)
+                .map(|(healer, _)| healer - pos),
+            EnemyState::Flee { .. } => threat.map(|threat| pos - threat),
+            EnemyState::Kite => melee_threat.map(|threat| pos - threat),
+            _ => None,
+        };
+        if let Some(direction) = direction {
+            tr.translation += direction.normalize_or_zero().extend(0.)
+                * vel.0
+                * time.delta_seconds();
+        }
+    }
+}
+
+pub struct EnemyAiPlugin;
+
+impl Plugin for EnemyAiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(enemy_behavior_system),
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use anyhow::{anyhow, bail};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boss::{BossAbility, BossDescriptor},
+    combat::AttackType,
+    enemy_ai::BehaviorDescriptor,
+    enemy_logic::EnemyId,
+    loot::{LootDrop, LootTable},
+    raider::RaiderDescriptor,
+    targeting::TargetPolicy,
+};
+
+const ARCHETYPES_FILE: &str = "enemies.archetypes.json";
+
+/// Not an enemy, spawn groups use it to drop a bloodrock node in the arena
+pub const BLOODROCK_NODE: &str = "BloodrockNode";
+
+/// A sprite sheet enemy sprites are taken from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasDescriptor {
+    /// Relative to the assets folder
+    pub path: String,
+    pub tile_size: [f32; 2],
+    pub columns: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpriteDescriptor {
+    /// Key into the atlases of the archetype file
+    pub atlas: String,
+    pub index: usize,
+    /// Tints the sprite, to tell apart enemies sharing one
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CombatDescriptor {
+    pub attack_type: AttackType,
+    pub damage: f32,
+    /// Seconds
+    pub time_between_attacks: f32,
+    pub attack_range: f32,
+    #[serde(default)]
+    pub piercing: f32,
+    #[serde(default)]
+    pub targeting: TargetPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TankDescriptor {
+    /// Seconds
+    pub time_between_taunts: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealerDescriptor {
+    pub heal_amount: f32,
+    pub range: f32,
+    /// Seconds
+    pub time_between_heals: f32,
+}
+
+/// Everything needed to spawn one kind of enemy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnemyArchetype {
+    pub sprite: SpriteDescriptor,
+    pub health: f32,
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    #[serde(default = "default_collider_radius")]
+    pub collider_radius: f32,
+    /// Moves the sorting point of big sprites down to their feet
+    #[serde(default)]
+    pub z_offset: Option<f32>,
+    #[serde(default = "default_hp_bar_width")]
+    pub hp_bar_width: f32,
+    /// Lower ranks stand in front in spawn formations
+    #[serde(default = "default_rank")]
+    pub rank: u8,
+    /// Enemies without one do not attack
+    #[serde(default)]
+    pub combat: Option<CombatDescriptor>,
+    #[serde(default)]
+    pub tank: Option<TankDescriptor>,
+    #[serde(default)]
+    pub healer: Option<HealerDescriptor>,
+    /// Rolled on death, a single bloodrock if missing
+    #[serde(default)]
+    pub drops: LootTable,
+    #[serde(default)]
+    pub behavior: BehaviorDescriptor,
+    /// Goes after harvesters and bloodrock nodes instead of fighting
+    #[serde(default)]
+    pub raider: Option<RaiderDescriptor>,
+    /// Makes the enemy a boss with a health bar on top of the screen
+    #[serde(default)]
+    pub boss: Option<BossDescriptor>,
+}
+
+fn default_speed() -> f32 {
+    150.
+}
+
+fn default_collider_radius() -> f32 {
+    50.
+}
+
+fn default_hp_bar_width() -> f32 {
+    60.
+}
+
+fn default_rank() -> u8 {
+    1
+}
+
+/// Enemy archetypes as they are stored in `assets/enemies.archetypes.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0b8d7f5e-3c2a-4e61-9a4f-7d1e6c5b2a90"]
+pub struct ArchetypesAsset {
+    pub atlases: HashMap<String, AtlasDescriptor>,
+    pub enemies: HashMap<EnemyId, EnemyArchetype>,
+}
+
+pub fn parse_archetypes(
+    bytes: &[u8],
+) -> Result<ArchetypesAsset, anyhow::Error> {
+    let asset: ArchetypesAsset = serde_json::from_slice(bytes)
+        .map_err(|err| anyhow!("malformed archetype file: {}", err))?;
+    for (name, atlas) in asset.atlases.iter() {
+        if atlas.columns == 0 || atlas.rows == 0 {
+            bail!("atlas {}: needs at least one column and row", name);
+        }
+    }
+    for (id, enemy) in asset.enemies.iter() {
+        if id.0 == BLOODROCK_NODE {
+            bail!("{} is reserved for bloodrock nodes", BLOODROCK_NODE);
+        }
+        let atlas =
+            asset.atlases.get(&enemy.sprite.atlas).ok_or_else(|| {
+                anyhow!("{}: unknown atlas {}", id, enemy.sprite.atlas)
+            })?;
+        if enemy.sprite.index >= atlas.columns * atlas.rows {
+            bail!(
+                "{}: sprite index {} is outside of atlas {}",
+                id,
+                enemy.sprite.index,
+                enemy.sprite.atlas
+            );
+        }
+        if enemy.health <= 0. {
+            bail!("{}: health has to be positive", id);
+        }
+        validate_drops(id, &enemy.drops)?;
+        validate_behavior(id, &enemy.behavior)?;
+        if let Some(raider) = &enemy.raider {
+            if raider.capacity == 0
+                || raider.steal_amount == 0
+                || raider.steal_time <= 0.
+            {
+                bail!("{}: raiders need to be able to steal something", id);
+            }
+            if enemy.combat.is_some() {
+                bail!("{}: raiders do not fight", id);
+            }
+        }
+        if let Some(boss) = &enemy.boss {
+            validate_boss(&asset, id, boss)?;
+        }
+    }
+    Ok(asset)
+}
+
+fn validate_behavior(
+    id: &EnemyId,
+    behavior: &BehaviorDescriptor,
+) -> Result<(), anyhow::Error> {
+    let fractions = [
+        behavior.retreat_health,
+        Some(behavior.recover_health),
+        behavior.flee_health,
+    ];
+    if fractions.iter().flatten().any(|f| *f <= 0. || *f > 1.) {
+        bail!(
+            "{}: behavior health fractions have to be between 0 and 1",
+            id
+        );
+    }
+    if let Some(retreat) = behavior.retreat_health {
+        if retreat >= behavior.recover_health {
+            bail!("{}: recover_health has to be above retreat_health", id);
+        }
+    }
+    if behavior.kite_distance.map_or(false, |d| d <= 0.)
+        || behavior.flee_time <= 0.
+    {
+        bail!("{}: kite_distance and flee_time have to be positive", id);
+    }
+    Ok(())
+}
+
+fn validate_drops(
+    id: &EnemyId,
+    drops: &LootTable,
+) -> Result<(), anyhow::Error> {
+    if drops
+        .entries
+        .iter()
+        .any(|entry| entry.weight < 0. || !entry.weight.is_finite())
+    {
+        bail!("{}: loot weights have to be zero or positive", id);
+    }
+    if drops.rolls > 0 && drops.entries.iter().all(|entry| entry.weight == 0.) {
+        bail!("{}: loot tables need an entry with a positive weight", id);
+    }
+    for entry in drops.entries.iter() {
+        let valid = match &entry.drop {
+            LootDrop::Nothing | LootDrop::ResourceNode => true,
+            LootDrop::Bloodrock { amount } => *amount > 0,
+            LootDrop::Buff {
+                multiplier,
+                duration,
+                ..
+            } => *multiplier > 0. && *duration > 0.,
+            LootDrop::Item {
+                name, multiplier, ..
+            } => !name.is_empty() && *multiplier > 0.,
+        };
+        if !valid {
+            bail!("{}: invalid loot {:?}", id, entry.drop);
+        }
+    }
+    Ok(())
+}
+
+fn validate_boss(
+    asset: &ArchetypesAsset,
+    id: &EnemyId,
+    boss: &BossDescriptor,
+) -> Result<(), anyhow::Error> {
+    match boss.phases.first() {
+        Some(phase) if phase.health_below == 1. => {}
+        Some(_) => bail!("{}: the first boss phase has to start at 1", id),
+        None => bail!("{}: bosses need at least one phase", id),
+    }
+    for (index, phase) in boss.phases.iter().enumerate() {
+        if phase.health_below <= 0. || phase.health_below > 1. {
+            bail!("{}: phase {} has to start between 0 and 1", id, index);
+        }
+        if index > 0
+            && phase.health_below >= boss.phases[index - 1].health_below
+        {
+            bail!("{}: phase {} has to start below the last one", id, index);
+        }
+        for ability in phase.abilities.iter() {
+            let positive = match ability {
+                BossAbility::Summon { adds, cooldown, .. } => {
+                    for add in adds.iter() {
+                        if add.0 != BLOODROCK_NODE
+                            && !asset.enemies.contains_key(add)
+                        {
+                            bail!("{}: summons unknown enemy {}", id, add);
+                        }
+                    }
+                    vec![*cooldown]
+                }
+                BossAbility::Slam {
+                    telegraph,
+                    cooldown,
+                    ..
+                } => vec![*telegraph, *cooldown],
+                BossAbility::Charge {
+                    duration, cooldown, ..
+                } => vec![*duration, *cooldown],
+                BossAbility::Enrage {
+                    damage,
+                    attack_speed,
+                    speed,
+                } => vec![*damage, *attack_speed, *speed],
+            };
+            if positive.iter().any(|value| *value <= 0.) {
+                bail!(
+                    "{}: ability {:?} needs positive timings and multipliers",
+                    id,
+                    ability
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct ArchetypesLoader;
+
+impl AssetLoader for ArchetypesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let archetypes = parse_archetypes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(archetypes));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["archetypes.json"]
+    }
+}
+
+/// Enemy archetypes of the loaded archetype file, with their sprite sheets
+#[derive(Default)]
+pub struct EnemyArchetypes {
+    pub handle: Handle<ArchetypesAsset>,
+    pub atlases: HashMap<String, Handle<TextureAtlas>>,
+    pub enemies: HashMap<EnemyId, EnemyArchetype>,
+}
+
+impl EnemyArchetypes {
+    pub fn is_loaded(&self) -> bool {
+        !self.enemies.is_empty()
+    }
+
+    pub fn get(&self, id: &EnemyId) -> Option<&EnemyArchetype> {
+        self.enemies.get(id)
+    }
+
+    /// Whether levels can spawn `id`, bloodrock nodes included
+    pub fn knows(&self, id: &EnemyId) -> bool {
+        id.0 == BLOODROCK_NODE || self.enemies.contains_key(id)
+    }
+
+    /// Bosses never roll elite affixes, a splitting boss would come back
+    /// with all of its phases
+    pub fn is_boss(&self, id: &EnemyId) -> bool {
+        self.get(id).map_or(false, |enemy| enemy.boss.is_some())
+    }
+
+    /// Unknown enemies and bloodrock nodes go to the back
+    pub fn rank(&self, id: &EnemyId) -> u8 {
+        self.get(id).map_or(u8::MAX, |enemy| enemy.rank)
+    }
+
+    /// Every enemy id and the bloodrock node, sorted by name
+    pub fn ids(&self) -> Vec<EnemyId> {
+        let mut ids: Vec<EnemyId> = self.enemies.keys().cloned().collect();
+        ids.push(EnemyId::from(BLOODROCK_NODE));
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        ids
+    }
+}
+
+fn load_archetypes_system(
+    asset_server: Res<AssetServer>,
+    mut archetypes: ResMut<EnemyArchetypes>,
+) {
+    archetypes.handle = asset_server.load(ARCHETYPES_FILE);
+}
+
+/// Picks up the archetypes once they finished loading, or got hot reloaded
+fn archetypes_asset_event_system(
+    mut events: EventReader<AssetEvent<ArchetypesAsset>>,
+    assets: Res<Assets<ArchetypesAsset>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut archetypes: ResMut<EnemyArchetypes>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle }
+            | AssetEvent::Modified { handle } => {
+                if *handle != archetypes.handle {
+                    continue;
+                }
+                let asset = match assets.get(handle) {
+                    Some(asset) => asset,
+                    None => continue,
+                };
+                info!("loaded {} enemy archetypes", asset.enemies.len());
+                archetypes.atlases = asset
+                    .atlases
+                    .iter()
+                    .map(|(name, atlas)| {
+                        let handle =
+                            texture_atlases.add(TextureAtlas::from_grid(
+                                asset_server.load(atlas.path.as_str()),
+                                Vec2::from(atlas.tile_size),
+                                atlas.columns,
+                                atlas.rows,
+                            ));
+                        (name.clone(), handle)
+                    })
+                    .collect();
+                archetypes.enemies = asset.enemies.clone();
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+pub struct EnemyArchetypePlugin;
+
+impl Plugin for EnemyArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ArchetypesAsset>()
+            .init_asset_loader::<ArchetypesLoader>()
+            .init_resource::<EnemyArchetypes>()
+            .add_startup_system(load_archetypes_system)
+            .add_system(archetypes_asset_event_system);
+    }
+}
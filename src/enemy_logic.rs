@@ -1,27 +1,36 @@
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 
 use crate::{
+    boss::Boss,
     collision,
-    combat::{AttackType, CombatComponent},
+    combat::CombatComponent,
+    difficulty::Difficulty,
+    elite::{Elite, EliteDescriptor},
+    enemy_ai::EnemyBehavior,
+    enemy_archetype::{EnemyArchetypes, BLOODROCK_NODE},
     game::{
-        spawn_bloodrock_node, AvoidOthers, DontSortZ,
+        spawn_bloodrock_node, AvoidOthers, BloodrockAmount, DontSortZ,
         MovementAnimationController, PlayerController, ResourceAssets,
         UnitType, Velocity, ZOffset,
     },
-    health::{hp_material, Health, SpawnResourceNodeOnDeath},
-    ui::{EndGameManager, EndGameState},
-    worker_logic::{
-        HealerComponent, HealingState, TankComponent, UnitFollowPlayer,
+    health::{hp_material, Health},
+    level::{
+        ArenaDescriptor, BackgroundDescriptor, DecorationDescriptor,
+        LevelAsset, SpawnGroupDescriptor, WaveTrigger,
     },
-    GameTime, SceneState,
+    loot::LootOnDeath,
+    objectives::ObjectiveDescriptor,
+    raider::Raider,
+    replay::PlayerInput,
+    rng::GameRng,
+    spawn_formation::{formation_slots, MarchIn},
+    targeting::Targeting,
+    worker_logic::{HealerComponent, HealingState, TankComponent},
+    GameMode, GameTime, SceneState,
 };
 use rand::Rng;
-#[derive(Default)]
-pub struct EnemyAssets {
-    enemies: Handle<TextureAtlas>,
-    boss1: Handle<TextureAtlas>,
-    boss2: Handle<TextureAtlas>,
-}
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 pub struct EnemyLogicPlugin;
 
 #[derive(Component)]
@@ -31,290 +40,147 @@ pub struct BasicEnemyLogic;
 pub struct EnemySpawner {
     pub time_between_spawns: Timer,
     pub distance_from_spawn_point: f32,
+    /// Enemies are picked at random from this pool, add the same type
+    /// multiple times to make it more likely
+    pub pool: Vec<EnemyId>,
+    pub stat_scale: EnemyStatScale,
+    pub elites: EliteDescriptor,
+}
+
+/// What an enemy got spawned as, so it can be spawned again from a save
+#[derive(Component, Clone)]
+pub struct EnemyKind {
+    pub kind: EnemyId,
+    pub stat_scale: EnemyStatScale,
 }
-#[derive(Clone, Copy)]
-enum EnemyTypesToSpawn {
-    Thrash,
-    Ranged,
-    Sworder,
-    Piker,
-    Armored,
-    Healer,
-    BloodrockNode,
-    Boss1,
-    Boss2,
+
+/// Multipliers applied to the base stats of spawned enemies
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct EnemyStatScale {
+    pub health: f32,
+    pub damage: f32,
+    pub attack_speed: f32,
+}
+
+impl Default for EnemyStatScale {
+    fn default() -> Self {
+        EnemyStatScale {
+            health: 1.,
+            damage: 1.,
+            attack_speed: 1.,
+        }
+    }
+}
+
+impl EnemyStatScale {
+    pub fn scale_health(&self, health: &Health) -> Health {
+        Health {
+            max_health: health.max_health * self.health,
+            current_health: health.current_health * self.health,
+            armor: health.armor,
+        }
+    }
+
+    pub fn scale_combat(&self, combat: &CombatComponent) -> CombatComponent {
+        let mut combat = combat.clone();
+        combat.damage *= self.damage;
+        let time_between_attacks =
+            combat.time_between_attacks.duration().as_secs_f32()
+                / self.attack_speed;
+        combat
+            .time_between_attacks
+            .set_duration(Duration::from_secs_f32(time_between_attacks));
+        combat
+    }
+}
+
+impl std::ops::Mul for EnemyStatScale {
+    type Output = EnemyStatScale;
+
+    fn mul(self, other: EnemyStatScale) -> EnemyStatScale {
+        EnemyStatScale {
+            health: self.health * other.health,
+            damage: self.damage * other.damage,
+            attack_speed: self.attack_speed * other.attack_speed,
+        }
+    }
+}
+
+/// Name of an enemy archetype, see [`EnemyArchetypes`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EnemyId(pub String);
+
+impl From<&str> for EnemyId {
+    fn from(id: &str) -> Self {
+        EnemyId(id.to_string())
+    }
+}
+
+impl std::fmt::Display for EnemyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 #[derive(Clone)]
 pub struct Wave {
-    spawn_data: Vec<(Vec<EnemyTypesToSpawn>, Vec3)>,
-    time_to_spawn_after_last_wave: Timer,
+    pub spawn_data: Vec<SpawnGroupDescriptor>,
+    pub time_to_spawn_after_last_wave: Timer,
+    pub trigger: WaveTrigger,
+    pub early_call_bonus: Option<usize>,
+    pub elites: EliteDescriptor,
 }
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Level {
     pub waves: Vec<Wave>,
     pub current_wave_index: usize,
+    pub bloodrock_nodes: Vec<Vec3>,
+    pub arena: ArenaDescriptor,
+    pub background: BackgroundDescriptor,
+    pub decorations: Option<Vec<DecorationDescriptor>>,
+    pub objectives: Vec<ObjectiveDescriptor>,
 }
-#[derive(Deref, DerefMut)]
 pub struct LevelManager {
     pub current_level: Level,
+    pub level_handle: Handle<LevelAsset>,
 }
 
-pub fn get_test_level() -> Level {
-    return Level {
-        waves: vec![
-            Wave {
-                spawn_data: vec![(
-                    vec![EnemyTypesToSpawn::Thrash, EnemyTypesToSpawn::Thrash],
-                    Vec3::new(0., 1300., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(3., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (vec![EnemyTypesToSpawn::Thrash], Vec3::new(0., 1200., 0.)),
-                    (
-                        vec![EnemyTypesToSpawn::Ranged],
-                        Vec3::new(-1300., 100., 0.),
-                    ),
-                    (
-                        vec![EnemyTypesToSpawn::Thrash],
-                        Vec3::new(1300., -100., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                        ],
-                        Vec3::new(0., 1200., 0.),
-                    ),
-                    (
-                        vec![EnemyTypesToSpawn::BloodrockNode],
-                        Vec3::new(-500., 100., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Ranged,
-                        EnemyTypesToSpawn::Thrash,
-                        EnemyTypesToSpawn::Thrash,
-                    ],
-                    Vec3::new(0., 1200., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(25., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Ranged,
-                        EnemyTypesToSpawn::Armored,
-                        EnemyTypesToSpawn::Thrash,
-                        EnemyTypesToSpawn::Thrash,
-                    ],
-                    Vec3::new(0., 1200., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(25., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Armored,
-                            EnemyTypesToSpawn::Healer,
-                        ],
-                        Vec3::new(0., 1200., 0.),
-                    ),
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                        ],
-                        Vec3::new(0., -1200., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(30., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Ranged,
-                        EnemyTypesToSpawn::Boss1,
-                        EnemyTypesToSpawn::Ranged,
-                    ],
-                    Vec3::new(0., 1200., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(30., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                        ],
-                        Vec3::new(-500., 1200., 0.),
-                    ),
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                        ],
-                        Vec3::new(500., -1200., 0.),
-                    ),
-                    (
-                        vec![EnemyTypesToSpawn::BloodrockNode],
-                        Vec3::new(500., -100., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(30., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Piker,
-                        EnemyTypesToSpawn::Piker,
-                        EnemyTypesToSpawn::Piker,
-                        EnemyTypesToSpawn::Piker,
-                    ],
-                    Vec3::new(1500., 0., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Armored,
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                        ],
-                        Vec3::new(-1500., 0., 0.),
-                    ),
-                    (
-                        vec![EnemyTypesToSpawn::BloodrockNode],
-                        Vec3::new(500., 100., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Thrash,
-                        EnemyTypesToSpawn::Thrash,
-                        EnemyTypesToSpawn::Ranged,
-                    ],
-                    Vec3::new(0., -1500., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Armored,
-                            EnemyTypesToSpawn::Armored,
-                        ],
-                        Vec3::new(1200., 0., 0.),
-                    ),
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Armored,
-                            EnemyTypesToSpawn::Armored,
-                        ],
-                        Vec3::new(-1200., 0., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(20., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                        ],
-                        Vec3::new(1200., 0., 0.),
-                    ),
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                            EnemyTypesToSpawn::Ranged,
-                        ],
-                        Vec3::new(-1200., 0., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(15., false),
-            },
-            Wave {
-                spawn_data: vec![
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                        ],
-                        Vec3::new(1200., 0., 0.),
-                    ),
-                    (
-                        vec![
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                            EnemyTypesToSpawn::Thrash,
-                        ],
-                        Vec3::new(-1200., 0., 0.),
-                    ),
-                ],
-                time_to_spawn_after_last_wave: Timer::from_seconds(5., false),
-            },
-            Wave {
-                spawn_data: vec![(
-                    vec![
-                        EnemyTypesToSpawn::Healer,
-                        EnemyTypesToSpawn::Healer,
-                        EnemyTypesToSpawn::Boss2,
-                    ],
-                    Vec3::new(-1200., 1200., 0.),
-                )],
-                time_to_spawn_after_last_wave: Timer::from_seconds(30., false),
-            },
-        ],
-        current_wave_index: 0,
-    };
+/// Playable area of the current level, enemies outside of it walk back in
+pub struct Arena {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl From<ArenaDescriptor> for Arena {
+    fn from(desc: ArenaDescriptor) -> Self {
+        Arena {
+            min: Vec2::from(desc.min),
+            max: Vec2::from(desc.max),
+        }
+    }
 }
 
 fn move_enemies_to_arena(
-    mut enemies: Query<(&mut Transform, &Velocity), With<BasicEnemyLogic>>,
+    mut enemies: Query<
+        (&mut Transform, &Velocity, Option<&Raider>),
+        (With<BasicEnemyLogic>, Without<MarchIn>),
+    >,
     time: Res<GameTime>,
+    arena: Res<Arena>,
 ) {
-    for (mut tr, vel) in enemies.iter_mut() {
-        if tr.translation.x < -1000.
-            || tr.translation.x > 700.
-            || tr.translation.y < -650.
-            || tr.translation.y > 650.
+    for (mut tr, vel, raider) in enemies.iter_mut() {
+        if raider.map_or(false, |raider| raider.is_escaping()) {
+            continue;
+        }
+        if tr.translation.x < arena.min.x
+            || tr.translation.x > arena.max.x
+            || tr.translation.y < arena.min.y
+            || tr.translation.y > arena.max.y
         {
-            let pos = tr.translation;
-            tr.translation += (Vec3::new(0., 0., 0.) - pos)
-                .truncate()
-                .normalize()
+            let center = (arena.min + arena.max) / 2.;
+            tr.translation += (center - tr.translation.truncate())
+                .normalize_or_zero()
                 .extend(0.)
                 * vel.0
                 * 1.2
@@ -326,52 +192,96 @@ fn move_enemies_to_arena(
 fn level_progresser_system(
     game_time: Res<GameTime>,
     mut level_manager: ResMut<LevelManager>,
-    enemy_assets: Res<EnemyAssets>,
+    archetypes: Res<EnemyArchetypes>,
     mut cmd: Commands,
     enemies: Query<Entity, With<BasicEnemyLogic>>,
     mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
     // FIXME: reuse the same mesh?
     mut mesh_assets: ResMut<Assets<Mesh>>,
-    mut end_game_state: ResMut<EndGameManager>,
     resource_assets: Res<ResourceAssets>,
+    game_mode: Res<GameMode>,
+    mut rng: ResMut<GameRng>,
+    input: Res<PlayerInput>,
+    player: Query<&Transform, With<PlayerController>>,
+    mut bloodrock: ResMut<BloodrockAmount>,
+    arena: Res<Arena>,
+    difficulty: Res<Difficulty>,
 ) {
-    //TODO: win level when last wave is spawned && no enemies left!
+    if *game_mode != GameMode::Campaign {
+        return;
+    }
+    // the level asset has not finished loading yet
+    if level_manager.current_level.waves.is_empty() {
+        return;
+    }
+    // winning is up to the objectives
     if level_manager.current_level.waves.len()
         <= level_manager.current_level.current_wave_index
     {
-        if enemies.iter().len() < 1 {
-            end_game_state.state = EndGameState::Win;
-        }
         return;
     } else {
         let current_wave_index = level_manager.current_level.current_wave_index;
         let current_wave =
             &mut level_manager.current_level.waves[current_wave_index];
+        let settings = difficulty.settings();
         current_wave
             .time_to_spawn_after_last_wave
-            .tick(game_time.delta());
-        if current_wave.time_to_spawn_after_last_wave.finished() {
-            let mut rng = rand::thread_rng();
-            for enemies in current_wave.spawn_data.iter_mut() {
-                let location = enemies.1;
-                for enemy in enemies.0.iter() {
-                    let spawn_location = location
-                        + Vec3::new(
-                            rng.gen_range(-1.0..=1.0),
-                            rng.gen_range(-1.0..=1.0),
-                            0.,
-                        )
-                        .normalize()
-                            * 500.;
-                    spawn_enemy_based_on_type(
-                        *enemy,
+            .tick(game_time.delta().div_f32(settings.wave_delay));
+        let triggered = current_wave.time_to_spawn_after_last_wave.finished()
+            && current_wave.trigger.is_met(
+                enemies.iter().len(),
+                player.get_single().ok().map(|tr| tr.translation.truncate()),
+            );
+        let early_call_bonus = if !triggered && input.call_wave {
+            current_wave.early_call_bonus
+        } else {
+            None
+        };
+        if let Some(bonus) = early_call_bonus {
+            bloodrock.0 += bonus;
+        }
+        if triggered || early_call_bonus.is_some() {
+            let rng = &mut rng.gameplay;
+            for group in current_wave.spawn_data.iter() {
+                let slots = formation_slots(
+                    &group.enemies,
+                    Vec2::from(group.position),
+                    group.formation,
+                    group.entry,
+                    &arena,
+                    |enemy| archetypes.rank(enemy),
+                    rng,
+                );
+                for slot in slots {
+                    let entity = spawn_enemy(
+                        &slot.enemy,
                         &mut cmd,
-                        &enemy_assets,
-                        spawn_location,
+                        &archetypes,
+                        slot.spawn_position.unwrap_or(slot.position),
                         &mut *hp_assets,
                         &mut *mesh_assets,
                         &resource_assets,
+                        &settings.enemy,
                     );
+                    let entity = match entity {
+                        Some(entity) => entity,
+                        None => continue,
+                    };
+                    if slot.enemy.0 == BLOODROCK_NODE {
+                        continue;
+                    }
+                    if slot.spawn_position.is_some() {
+                        cmd.entity(entity).insert(MarchIn {
+                            target: slot.position,
+                        });
+                    }
+                    if archetypes.is_boss(&slot.enemy) {
+                        continue;
+                    }
+                    let affixes = current_wave.elites.roll(rng);
+                    if !affixes.is_empty() {
+                        cmd.entity(entity).insert(Elite::new(affixes));
+                    }
                 }
             }
 
@@ -380,390 +290,57 @@ fn level_progresser_system(
     }
 }
 
-fn get_random_enemy() -> EnemyTypesToSpawn {
-    let enemy_types = [
-        EnemyTypesToSpawn::Thrash,
-        EnemyTypesToSpawn::Ranged,
-        EnemyTypesToSpawn::Sworder,
-    ];
-    let mut rng = rand::thread_rng();
-    return enemy_types[rng.gen_range(0..enemy_types.len())].clone();
+fn get_random_enemy<'a>(
+    pool: &'a [EnemyId],
+    rng: &mut impl Rng,
+) -> &'a EnemyId {
+    return &pool[rng.gen_range(0..pool.len())];
 }
 
-fn spawn_enemy_based_on_type(
-    enemy_type: EnemyTypesToSpawn,
-    mut cmd: &mut Commands,
-    enemy_assets: &EnemyAssets,
+/// Spawns any enemy of the archetype file, or a bloodrock node
+///
+/// Returns `None` if there is no archetype called `id`.
+pub fn spawn_enemy(
+    id: &EnemyId,
+    cmd: &mut Commands,
+    archetypes: &EnemyArchetypes,
     pos: Vec3,
     hp_assets: &mut Assets<hp_material::HpMaterial>,
     mesh_assets: &mut Assets<Mesh>,
     resource_assets: &ResourceAssets,
-) {
-    let mut spawn_enemy = |health: Health,
-                           combat_compo: Option<&CombatComponent>,
-                           index: usize|
-     -> Entity {
-        spawn_regular_enemy(
-            &mut cmd,
-            pos,
-            &mut *hp_assets,
-            &mut *mesh_assets,
-            &health,
-            combat_compo,
-            &enemy_assets.enemies,
-            index,
-        )
-    };
-
-    match enemy_type {
-        EnemyTypesToSpawn::Thrash => {
-            spawn_enemy(
-                Health {
-                    current_health: 3.,
-                    max_health: 3.,
-                    armor: 0.,
-                },
-                Some(&CombatComponent {
-                    target_type: UnitType::Ally,
-                    attack_type: AttackType::Melee,
-                    damage: 0.6,
-                    time_between_attacks: Timer::from_seconds(1., true),
-                    attack_range: 80.,
-                    piercing: 0.,
-                    ..Default::default()
-                }),
-                0,
-            );
-        }
-        EnemyTypesToSpawn::Ranged => {
-            spawn_enemy(
-                Health {
-                    current_health: 5.,
-                    max_health: 5.,
-                    armor: 0.,
-                },
-                Some(&CombatComponent {
-                    target_type: UnitType::Ally,
-                    attack_type: AttackType::Ranged,
-                    damage: 1.,
-                    time_between_attacks: Timer::from_seconds(1., true),
-                    attack_range: 200.,
-                    piercing: 0.,
-                    ..Default::default()
-                }),
-                2,
-            );
-        }
-        EnemyTypesToSpawn::Sworder => {
-            spawn_enemy(
-                Health {
-                    current_health: 7.,
-                    max_health: 7.,
-                    armor: 0.,
-                },
-                Some(&CombatComponent {
-                    target_type: UnitType::Ally,
-                    attack_type: AttackType::Melee,
-                    damage: 1.3,
-                    time_between_attacks: Timer::from_seconds(1., true),
-                    attack_range: 80.,
-                    piercing: 0.,
-                    ..Default::default()
-                }),
-                1,
-            );
-        }
-        EnemyTypesToSpawn::Piker => {
-            spawn_enemy(
-                Health {
-                    current_health: 7.,
-                    max_health: 7.,
-                    armor: 0.,
-                },
-                Some(&CombatComponent {
-                    target_type: UnitType::Ally,
-                    attack_type: AttackType::Melee,
-                    damage: 1.25,
-                    time_between_attacks: Timer::from_seconds(1.3, true),
-                    attack_range: 120.,
-                    piercing: 0.75,
-                    ..Default::default()
-                }),
-                3,
-            );
-        }
-        EnemyTypesToSpawn::Armored => {
-            let entity = spawn_enemy(
-                Health {
-                    current_health: 15.,
-                    max_health: 15.,
-                    armor: 0.8,
-                },
-                Some(&CombatComponent {
-                    target_type: UnitType::Ally,
-                    attack_type: AttackType::Melee,
-                    damage: 0.2,
-                    time_between_attacks: Timer::from_seconds(2., true),
-                    attack_range: 80.,
-                    piercing: 0.,
-                    ..Default::default()
-                }),
-                4,
-            );
-            cmd.entity(entity).insert(TankComponent {
-                time_between_taunts: Timer::from_seconds(3., true),
-                target_type: UnitType::Ally,
-            });
-        }
-        EnemyTypesToSpawn::Healer => {
-            let entity = spawn_enemy(
-                Health {
-                    current_health: 7.,
-                    max_health: 7.,
-                    armor: 0.,
-                },
-                None,
-                5,
-            );
-            cmd.entity(entity).insert(HealerComponent {
-                heal_amount: 0.2,
-                range: 200.,
-                time_between_heals: Timer::from_seconds(2., true),
-                target: None,
-                state: HealingState::Idle,
-                target_type: UnitType::Enemy,
-            });
-        }
-        EnemyTypesToSpawn::Boss1 => {
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: enemy_assets.boss1.clone(),
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert_bundle(collision::AABBBundle {
-                desc: collision::AABBDescriptor {
-                    radius: Vec3::splat(50.),
-                },
-                filter: collision::CollisionFilter {
-                    self_layers: collision::CollisionType::WORKER,
-                    collisions_mask:
-                        collision::CollisionType::WORKER_COLLISIONS,
-                },
-                ..Default::default()
-            })
-            .insert(Health {
-                current_health: 30.,
-                max_health: 30.,
-                armor: 0.,
-            })
-            .insert(CombatComponent {
-                target_type: UnitType::Ally,
-                attack_type: AttackType::Melee,
-                damage: 1.,
-                time_between_attacks: Timer::from_seconds(1., true),
-                attack_range: 80.,
-                piercing: 0.,
-                ..Default::default()
-            })
-            .insert(Transform::from_translation(pos))
-            .insert(Velocity(150.))
-            .insert(BasicEnemyLogic)
-            .insert(SpawnResourceNodeOnDeath { chance: 0. })
-            .insert(AvoidOthers { is_enabled: true })
-            .insert(MovementAnimationController {
-                is_moving: false,
-                last_frame_pos: pos,
-                time_to_stop_moving: Timer::from_seconds(0.3, false),
-            })
-            .with_children(|cmd| {
-                cmd.spawn_bundle(MaterialMesh2dBundle {
-                    mesh: bevy::sprite::Mesh2dHandle(mesh_assets.add(
-                        Mesh::from(shape::Quad {
-                            size: Vec2::new(100.0, 10.0),
-                            flip: false,
-                        }),
-                    )),
-                    material: hp_assets.add(hp_material::HpMaterial {
-                        color_empty: Color::RED,
-                        color_full: Color::ORANGE_RED,
-                        hp: 50.0,
-                        hp_max: 100.0,
-                    }),
-                    transform: Transform::from_translation(
-                        Vec3::Z * 200.0 + Vec3::Y * 60.0,
-                    ),
-                    ..Default::default()
-                })
-                .insert(DontSortZ);
-            });
-        }
-        EnemyTypesToSpawn::Boss2 => {
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: enemy_assets.boss2.clone(),
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert_bundle(collision::AABBBundle {
-                desc: collision::AABBDescriptor {
-                    radius: Vec3::splat(50.),
-                },
-                filter: collision::CollisionFilter {
-                    self_layers: collision::CollisionType::WORKER,
-                    collisions_mask:
-                        collision::CollisionType::WORKER_COLLISIONS,
-                },
-                ..Default::default()
-            })
-            .insert(Health {
-                current_health: 30.,
-                max_health: 30.,
-                armor: 0.,
-            })
-            .insert(CombatComponent {
-                target_type: UnitType::Ally,
-                attack_type: AttackType::Ranged,
-                damage: 0.5,
-                time_between_attacks: Timer::from_seconds(0.5, true),
-                attack_range: 200.,
-                piercing: 0.,
-                ..Default::default()
-            })
-            .insert(Transform::from_translation(pos))
-            .insert(Velocity(150.))
-            .insert(BasicEnemyLogic)
-            .insert(SpawnResourceNodeOnDeath { chance: 0. })
-            .insert(AvoidOthers { is_enabled: true })
-            .insert(MovementAnimationController {
-                is_moving: false,
-                last_frame_pos: pos,
-                time_to_stop_moving: Timer::from_seconds(0.3, false),
-            })
-            .with_children(|cmd| {
-                cmd.spawn_bundle(MaterialMesh2dBundle {
-                    mesh: bevy::sprite::Mesh2dHandle(mesh_assets.add(
-                        Mesh::from(shape::Quad {
-                            size: Vec2::new(100.0, 10.0),
-                            flip: false,
-                        }),
-                    )),
-                    material: hp_assets.add(hp_material::HpMaterial {
-                        color_empty: Color::RED,
-                        color_full: Color::ORANGE_RED,
-                        hp: 50.0,
-                        hp_max: 100.0,
-                    }),
-                    transform: Transform::from_translation(
-                        Vec3::Z * 200.0 + Vec3::Y * 60.0,
-                    ),
-                    ..Default::default()
-                })
-                .insert(DontSortZ);
-            });
-        }
-        EnemyTypesToSpawn::BloodrockNode => {
-            spawn_bloodrock_node(&mut cmd, &resource_assets, pos)
-        }
+    scale: &EnemyStatScale,
+) -> Option<Entity> {
+    if id.0 == BLOODROCK_NODE {
+        return Some(spawn_bloodrock_node(cmd, resource_assets, pos));
     }
-}
-
-fn enemy_targetting_logic_system(
-    mut enemies: Query<
-        (&mut CombatComponent, &GlobalTransform),
-        With<BasicEnemyLogic>,
-    >,
-    allys: Query<
-        (Entity, &GlobalTransform),
-        (With<UnitFollowPlayer>, Without<BasicEnemyLogic>),
-    >,
-    player: Query<Entity, With<PlayerController>>,
-) {
-    //TODO: find closest ally that the enemy can attack
-    for (mut enemy_combat, enemy_tr) in enemies.iter_mut() {
-        let mut closest_target = (None, 99999.);
-        for (ally_entity, ally_tr) in allys.iter() {
-            if (enemy_tr.translation().truncate()
-                - ally_tr.translation().truncate())
-            .length()
-                < closest_target.1
-            {
-                closest_target = (
-                    Some(ally_entity),
-                    (enemy_tr.translation().truncate()
-                        - ally_tr.translation().truncate())
-                    .length(),
-                );
-            }
-        }
-        if enemy_combat.target == None {
-            enemy_combat.target = closest_target.0;
-        }
-
-        if enemy_combat.target == None {
-            for p in player.iter() {
-                enemy_combat.target = Some(p);
-            }
-        }
-    }
-}
-
-fn enemy_spawner_system(
-    time: Res<GameTime>,
-    enemy_assets: Res<EnemyAssets>,
-    mut cmd: Commands,
-    mut enemy_spawners: Query<(&mut EnemySpawner, &GlobalTransform)>,
-    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
-    // FIXME: reuse the same mesh?
-    mut mesh_assets: ResMut<Assets<Mesh>>,
-    resource_assets: Res<ResourceAssets>,
-) {
-    let mut rng = rand::thread_rng();
-    for (mut enemy_spawner, global_tr) in enemy_spawners.iter_mut() {
-        enemy_spawner.time_between_spawns.tick(time.delta());
-        if enemy_spawner.time_between_spawns.finished() {
-            enemy_spawner.time_between_spawns.reset();
-
-            spawn_enemy_based_on_type(
-                get_random_enemy(),
-                &mut cmd,
-                &enemy_assets,
-                global_tr.translation()
-                    + (Vec3::new(
-                        rng.gen_range(-1.0..=1.0),
-                        rng.gen_range(-1.0..=1.0),
-                        0.,
-                    )
-                    .normalize()
-                        * enemy_spawner.distance_from_spawn_point),
-                &mut *hp_assets,
-                &mut *mesh_assets,
-                &resource_assets,
-            );
+    let archetype = match archetypes.get(id) {
+        Some(archetype) => archetype,
+        None => {
+            warn!("Unknown enemy {}, check the archetype file", id);
+            return None;
         }
-    }
-}
+    };
 
-fn spawn_regular_enemy(
-    cmd: &mut Commands,
-    pos: Vec3,
-    hp_assets: &mut Assets<hp_material::HpMaterial>,
-    mesh_assets: &mut Assets<Mesh>,
-    health: &Health,
-    combat_comp: Option<&CombatComponent>,
-    texture_atlas: &Handle<TextureAtlas>,
-    sprite_index: usize,
-) -> Entity {
-    let entity_id = cmd
+    let entity = cmd
         .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: texture_atlas.clone(),
+            texture_atlas: archetypes
+                .atlases
+                .get(&archetype.sprite.atlas)
+                .cloned()
+                .unwrap_or_default(),
             sprite: TextureAtlasSprite {
-                index: sprite_index,
+                index: archetype.sprite.index,
+                color: archetype
+                    .sprite
+                    .color
+                    .map_or(Color::WHITE, |[r, g, b]| Color::rgb(r, g, b)),
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert_bundle(collision::AABBBundle {
             desc: collision::AABBDescriptor {
-                radius: Vec3::splat(50.),
+                radius: Vec3::splat(archetype.collider_radius),
             },
             filter: collision::CollisionFilter {
                 self_layers: collision::CollisionType::WORKER,
@@ -771,22 +348,31 @@ fn spawn_regular_enemy(
             },
             ..Default::default()
         })
-        .insert(*health)
+        .insert(scale.scale_health(&Health {
+            current_health: archetype.health,
+            max_health: archetype.health,
+            armor: archetype.armor,
+        }))
         .insert(Transform::from_translation(pos))
-        .insert(Velocity(150.))
+        .insert(Velocity(archetype.speed))
         .insert(BasicEnemyLogic)
-        .insert(SpawnResourceNodeOnDeath { chance: 0. })
+        .insert(LootOnDeath(archetype.drops.clone()))
         .insert(AvoidOthers { is_enabled: true })
+        .insert(EnemyBehavior::new(archetype.behavior))
         .insert(MovementAnimationController {
             is_moving: false,
             last_frame_pos: pos,
             time_to_stop_moving: Timer::from_seconds(0.3, false),
         })
+        .insert(EnemyKind {
+            kind: id.clone(),
+            stat_scale: *scale,
+        })
         .with_children(|cmd| {
             cmd.spawn_bundle(MaterialMesh2dBundle {
                 mesh: bevy::sprite::Mesh2dHandle(mesh_assets.add(Mesh::from(
                     shape::Quad {
-                        size: Vec2::new(60.0, 10.0),
+                        size: Vec2::new(archetype.hp_bar_width, 10.0),
                         flip: false,
                     },
                 ))),
@@ -805,48 +391,114 @@ fn spawn_regular_enemy(
         })
         .id();
 
-    if let Some(combat_com) = combat_comp {
-        cmd.entity(entity_id).insert(combat_com.clone());
+    if let Some(offset) = archetype.z_offset {
+        cmd.entity(entity).insert(ZOffset { offset });
+    }
+    if let Some(combat) = &archetype.combat {
+        cmd.entity(entity)
+            .insert(scale.scale_combat(&CombatComponent {
+                target_type: UnitType::Ally,
+                attack_type: combat.attack_type,
+                damage: combat.damage,
+                time_between_attacks: Timer::from_seconds(
+                    combat.time_between_attacks,
+                    true,
+                ),
+                attack_range: combat.attack_range,
+                piercing: combat.piercing,
+                ..Default::default()
+            }))
+            .insert(Targeting::new(combat.targeting));
     }
-    return entity_id;
+    if let Some(tank) = &archetype.tank {
+        cmd.entity(entity).insert(TankComponent {
+            time_between_taunts: Timer::from_seconds(
+                tank.time_between_taunts,
+                true,
+            ),
+            target_type: UnitType::Ally,
+        });
+    }
+    if let Some(healer) = &archetype.healer {
+        cmd.entity(entity).insert(HealerComponent {
+            heal_amount: healer.heal_amount,
+            range: healer.range,
+            time_between_heals: Timer::from_seconds(
+                healer.time_between_heals,
+                true,
+            ),
+            target: None,
+            state: HealingState::Idle,
+            target_type: UnitType::Enemy,
+        });
+    }
+    if let Some(raider) = &archetype.raider {
+        cmd.entity(entity).insert(Raider::new(raider));
+    }
+    if let Some(boss) = &archetype.boss {
+        cmd.entity(entity).insert(Boss::new(boss));
+    }
+    Some(entity)
 }
 
-fn setup_system(
-    mut enemy_assets: ResMut<EnemyAssets>,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+fn enemy_spawner_system(
+    time: Res<GameTime>,
+    archetypes: Res<EnemyArchetypes>,
+    mut cmd: Commands,
+    mut enemy_spawners: Query<(&mut EnemySpawner, &GlobalTransform)>,
+    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
+    // FIXME: reuse the same mesh?
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    resource_assets: Res<ResourceAssets>,
+    mut rng: ResMut<GameRng>,
 ) {
-    enemy_assets.enemies = texture_atlases.add(TextureAtlas::from_grid(
-        asset_server.load("sprites/enemies/enemies.png"),
-        Vec2::new(122., 115.),
-        6,
-        1,
-    ));
-    enemy_assets.boss1 = texture_atlases.add(TextureAtlas::from_grid(
-        asset_server.load("sprites/enemies/boss1.png"),
-        Vec2::new(157., 244.),
-        1,
-        1,
-    ));
-    enemy_assets.boss2 = texture_atlases.add(TextureAtlas::from_grid(
-        asset_server.load("sprites/enemies/boss2.png"),
-        Vec2::new(185., 225.),
-        1,
-        1,
-    ));
+    let rng = &mut rng.gameplay;
+    for (mut enemy_spawner, global_tr) in enemy_spawners.iter_mut() {
+        enemy_spawner.time_between_spawns.tick(time.delta());
+        if enemy_spawner.time_between_spawns.finished() {
+            enemy_spawner.time_between_spawns.reset();
+            if enemy_spawner.pool.is_empty() {
+                continue;
+            }
+
+            let kind = get_random_enemy(&enemy_spawner.pool, rng);
+            let entity = spawn_enemy(
+                kind,
+                &mut cmd,
+                &archetypes,
+                global_tr.translation()
+                    + (Vec3::new(
+                        rng.gen_range(-1.0..=1.0),
+                        rng.gen_range(-1.0..=1.0),
+                        0.,
+                    )
+                    .normalize()
+                        * enemy_spawner.distance_from_spawn_point),
+                &mut *hp_assets,
+                &mut *mesh_assets,
+                &resource_assets,
+                &enemy_spawner.stat_scale,
+            );
+            if let (Some(entity), false) = (entity, archetypes.is_boss(kind)) {
+                let affixes = enemy_spawner.elites.roll(rng);
+                if !affixes.is_empty() {
+                    cmd.entity(entity).insert(Elite::new(affixes));
+                }
+            }
+        }
+    }
 }
 
 impl Plugin for EnemyLogicPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(EnemyAssets::default())
+        app.insert_resource(Arena::from(ArenaDescriptor::default()))
             .insert_resource(LevelManager {
-                current_level: get_test_level(),
+                current_level: Level::default(),
+                level_handle: Handle::default(),
             })
-            .add_startup_system(setup_system)
             .add_system_set(
                 SystemSet::on_update(SceneState::InGame)
                     .with_system(enemy_spawner_system)
-                    .with_system(enemy_targetting_logic_system)
                     .with_system(level_progresser_system)
                     .with_system(move_enemies_to_arena),
             );
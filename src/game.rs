@@ -2,19 +2,23 @@ use crate::{
     animation::{Animation, RotationAnimation},
     audio::{AudioAssets, PlayAudioEventPositional},
     collision,
+    difficulty::Difficulty,
     easing::Easing,
-    enemy_logic::BasicEnemyLogic,
+    enemy_logic::{Arena, BasicEnemyLogic, LevelManager},
     get_children_recursive,
     health::{hp_material, DestroyEntity, Health, HealthChangedEvent},
     interaction::MouseFollow,
     lerp::lerp_f32,
+    level::{default_decorations, BackgroundDescriptor},
+    orders::UnitOrder,
     particles,
+    replay::PlayerInput,
+    rng::GameRng,
     ui::{EndGameManager, EndGameState},
     worker_logic::{
-        change_class, CanEatWorker, UnitClass, UnitFollowPlayer, UnitSize,
-        WorkerHead,
+        CanEatWorker, UnitClass, UnitFollowPlayer, UnitSize, WorkerHead,
     },
-    DontDestroyBetweenLevels, GameTime, PlayerCamera, SceneState, Selectable,
+    GameMode, GameTime, PlayerCamera, SceneState, Selectable,
 };
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use rand::Rng;
@@ -22,6 +26,11 @@ use std::time::Duration;
 
 pub struct GamePlugin;
 
+/// Base values, the difficulty scales them
+pub const STARTING_BLOODROCK: usize = 20;
+pub const UNIT_COST: usize = 10;
+pub const MAX_SUPPLY: usize = 15;
+
 #[derive(Default, Component)]
 pub struct PlayerController;
 
@@ -50,6 +59,7 @@ pub struct GameAssets {
     pub hp_mesh: Handle<Mesh>,
     pub background: Handle<TextureAtlas>,
     pub forests: Handle<TextureAtlas>,
+    pub font: Handle<Font>,
 }
 
 #[derive(Default)]
@@ -143,6 +153,7 @@ fn harvester_logic_system(
         &mut AvoidOthers,
         &GlobalTransform,
         &Velocity,
+        Option<&UnitOrder>,
     )>,
     mut nodes: Query<
         (&GlobalTransform, &mut BloodrockNode, Entity),
@@ -157,9 +168,19 @@ fn harvester_logic_system(
 ) {
     for player_p in player_pos_q.iter() {
         let player_pos = player_p.translation().truncate();
-        for (mut harvester, mut tr, mut avoid_others, global_tr, velocity) in
-            harvesters.iter_mut()
+        for (
+            mut harvester,
+            mut tr,
+            mut avoid_others,
+            global_tr,
+            velocity,
+            order,
+        ) in harvesters.iter_mut()
         {
+            // walking somewhere they were ordered to
+            if matches!(order, Some(UnitOrder::MoveTo(_))) {
+                continue;
+            }
             if let Some(target) = harvester.target_node {
                 avoid_others.is_enabled = false;
                 if let Ok((node_tr, mut resource_node, _)) =
@@ -211,26 +232,26 @@ fn harvester_logic_system(
                     harvester.target_node = None;
                 }
             } else {
-                avoid_others.is_enabled = harvester.current_carried_resource
-                    != harvester.max_carryable_resource;
-
-                if (player_pos - global_tr.translation().truncate()).length()
-                    < 100.
-                {
-                    if harvester.current_carried_resource
-                        == harvester.max_carryable_resource
-                    {
+                let full = harvester.current_carried_resource
+                    == harvester.max_carryable_resource;
+                avoid_others.is_enabled = !full;
+
+                // full harvesters bring their load to the player, idle ones
+                // wait in their formation slot
+                if full {
+                    let to_player =
+                        player_pos - global_tr.translation().truncate();
+                    if to_player.length() < 100. {
                         life_soul_amount.0 +=
                             harvester.current_carried_resource;
                         info!("Soul amount: {}", life_soul_amount.0);
                         harvester.current_carried_resource = 0;
-                    }
-                } else {
-                    let dir = (player_pos - global_tr.translation().truncate())
-                        .extend(0.)
-                        .normalize();
+                    } else {
+                        let dir = to_player.extend(0.).normalize();
 
-                    tr.translation += dir * time.delta_seconds() * velocity.0;
+                        tr.translation +=
+                            dir * time.delta_seconds() * velocity.0;
+                    }
                 }
 
                 //IF HAND IS NOT FULL - CHECK IF THERE'S A NODE NEARBY
@@ -308,6 +329,7 @@ fn avoid_others_system(
     >,
     mut transforms: Query<&mut Transform>,
     time: Res<GameTime>,
+    mut rng: ResMut<GameRng>,
 ) {
     let mut change_these_vec: Vec<(Entity, Vec3)> = vec![];
     for player_tr in player.iter() {
@@ -336,10 +358,9 @@ fn avoid_others_system(
             if let Ok(mut tr) = transforms.get_mut(*e) {
                 let mut direction = *dir;
                 if direction == Vec3::ZERO {
-                    let mut rng = rand::thread_rng();
                     direction = Vec3::new(
-                        rng.gen_range(-1.0..=1.0),
-                        rng.gen_range(-1.0..=1.0),
+                        rng.gameplay.gen_range(-1.0..=1.0),
+                        rng.gameplay.gen_range(-1.0..=1.0),
                         0.,
                     );
                 }
@@ -396,7 +417,7 @@ fn z_sorter_system(
     }
 }
 
-fn handle_keyboard_movement(
+pub(crate) fn handle_keyboard_movement(
     delta: &mut Vec2,
     pressed_space: &mut bool,
     pressed_f: &mut bool,
@@ -470,7 +491,7 @@ fn change_player_size_based_on_bloodrock(
 fn player_controll_system(
     mut q_player: Query<&mut Transform, With<PlayerController>>,
     workers: Query<Entity, With<UnitFollowPlayer>>,
-    inputs: Res<Input<KeyCode>>,
+    input: Res<PlayerInput>,
     time: Res<GameTime>,
     mut cmd: Commands,
     game_assets: Res<GameAssets>,
@@ -482,17 +503,14 @@ fn player_controll_system(
     mut send_audio_event: EventWriter<PlayAudioEventPositional>,
     mut send_health_changed_event: EventWriter<HealthChangedEvent>,
     enemies: Query<(&GlobalTransform, Entity), With<BasicEnemyLogic>>,
+    mut rng: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
 ) {
     let delta_time = time.delta_seconds();
-    let mut delta_movement = Vec2::new(0., 0.);
-    let mut pressed_space = false;
-    let mut pressed_f = false;
-    handle_keyboard_movement(
-        &mut delta_movement,
-        &mut pressed_space,
-        &mut pressed_f,
-        &inputs,
-    );
+    let unit_cost = difficulty.settings().unit_cost(UNIT_COST);
+    let delta_movement = Vec2::from(input.movement);
+    let pressed_space = input.spawn_unit;
+    let pressed_f = input.damage_pulse;
 
     let player_speed = 300.;
 
@@ -502,15 +520,15 @@ fn player_controll_system(
         let mut spawn_particles = false;
         if pressed_space
             && workers.iter().len() < max_supply.0
-            && bloodrock.0 >= 10
+            && bloodrock.0 >= unit_cost
         {
             spawn_particles = true;
             send_audio_event.send(PlayAudioEventPositional {
                 sound: audio_assets.spawning_unit.clone(),
                 position: tr.translation,
             });
-            bloodrock.0 -= 10;
-            let mut rng = rand::thread_rng();
+            bloodrock.0 -= unit_cost;
+            let rng = &mut rng.gameplay;
             let index = rng.gen_range(0..=2);
             let spawn_point = tr.translation
                 + Vec3::new(
@@ -528,7 +546,7 @@ fn player_controll_system(
                     spawn_point,
                     UnitClass::Worker,
                     &mut *hp_assets,
-                )
+                );
             } else if index == 1 {
                 spawn_unit_with_class(
                     &mut cmd,
@@ -561,6 +579,7 @@ fn player_controll_system(
                         target: enemy_e,
                         amount: -1.5,
                         piercing: 0.5,
+                        source: None,
                     });
                 }
             }
@@ -682,6 +701,7 @@ fn setup_game(
             1,
             1,
         ));
+    game_assets.font = asset_server.load("fonts/FiraSans-Bold.ttf");
     game_assets.circle_sprite = texture_atlases.add(TextureAtlas::from_grid(
         asset_server.load("sprites/misc/circle.png"),
         Vec2::new(50., 50.),
@@ -716,120 +736,13 @@ fn setup_game(
         1,
         1,
     ));
-
-    //Make background / borders
-    for c in -2..=2 {
-        for r in -2..=2 {
-            let mut background_tr = Transform::from_scale(Vec3::splat(1.2));
-            background_tr.translation =
-                Vec3::new(c as f32 * 1000. * 1.2, r as f32 * 1000. * 1.2, 0.);
-
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.background.clone(),
-                transform: background_tr,
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: 10000. })
-            .insert(DontDestroyBetweenLevels);
-        }
-    }
-
-    //generate trees
-    let mut rng = rand::thread_rng();
-    for c in -5..=5 {
-        for r in 0..=5 {
-            let mut forest_tr: Transform =
-                Transform::from_scale(Vec3::splat(2.2));
-            forest_tr.translation = Vec3::new(
-                c as f32 * 242. * 2.2 + r as f32 * 60.,
-                700. + r as f32 * 100.,
-                0.,
-            );
-
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.forests.clone(),
-                transform: forest_tr,
-                sprite: TextureAtlasSprite {
-                    flip_x: rng.gen::<bool>(),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert(DontDestroyBetweenLevels);
-        }
-    }
-    for c in -5..=5 {
-        for r in 0..=5 {
-            let mut forest_tr: Transform =
-                Transform::from_scale(Vec3::splat(2.2));
-            forest_tr.translation = Vec3::new(
-                c as f32 * 242. * 2.2 + r as f32 * 60.,
-                -1000. + r as f32 * 100.,
-                0.,
-            );
-
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.forests.clone(),
-                transform: forest_tr,
-                sprite: TextureAtlasSprite {
-                    flip_x: rng.gen::<bool>(),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert(DontDestroyBetweenLevels);
-        }
-    }
-
-    for i in -1..=0 {
-        for r in -4..=6 {
-            let mut forest_tr: Transform =
-                Transform::from_scale(Vec3::splat(2.2));
-            forest_tr.translation =
-                Vec3::new(-1500. + i as f32 * 550., r as f32 * 100., 0.);
-
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.forests.clone(),
-                transform: forest_tr,
-                sprite: TextureAtlasSprite {
-                    flip_x: rng.gen::<bool>(),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert(DontDestroyBetweenLevels);
-        }
-    }
-    for i in -1..=0 {
-        for r in -4..=6 {
-            let mut forest_tr: Transform =
-                Transform::from_scale(Vec3::splat(2.2));
-            forest_tr.translation =
-                Vec3::new(1500. + i as f32 * 550., r as f32 * 100., 0.);
-
-            cmd.spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.forests.clone(),
-                transform: forest_tr,
-                sprite: TextureAtlasSprite {
-                    flip_x: rng.gen::<bool>(),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(ZOffset { offset: -100. })
-            .insert(DontDestroyBetweenLevels);
-        }
-    }
 }
 
 pub fn spawn_bloodrock_node(
     cmd: &mut Commands,
     resource_assets: &ResourceAssets,
     pos: Vec3,
-) {
+) -> Entity {
     cmd.spawn_bundle(SpriteSheetBundle {
         texture_atlas: resource_assets.bloodrock_node.clone(),
         ..Default::default()
@@ -837,17 +750,69 @@ pub fn spawn_bloodrock_node(
     .insert(BloodrockNode {
         amount_of_resource: 100,
     })
-    .insert(Transform::from_translation(pos));
+    .insert(Transform::from_translation(pos))
+    .id()
+}
+
+/// Background tiles of the level, destroyed with it
+pub fn spawn_background(
+    cmd: &mut Commands,
+    game_assets: &GameAssets,
+    background: &BackgroundDescriptor,
+) -> Vec<Entity> {
+    let mut tiles = Vec::new();
+    let tile_size = 1000. * background.scale;
+    for c in background.min_tile[0]..=background.max_tile[0] {
+        for r in background.min_tile[1]..=background.max_tile[1] {
+            let mut background_tr =
+                Transform::from_scale(Vec3::splat(background.scale));
+            background_tr.translation =
+                Vec3::new(c as f32 * tile_size, r as f32 * tile_size, 0.);
+
+            let tile = cmd
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: game_assets.background.clone(),
+                    transform: background_tr,
+                    ..Default::default()
+                })
+                .insert(ZOffset { offset: 10000. })
+                .id();
+            tiles.push(tile);
+        }
+    }
+    tiles
+}
+
+/// Forest placed by the level, destroyed with it
+pub fn spawn_forest(
+    cmd: &mut Commands,
+    game_assets: &GameAssets,
+    pos: Vec3,
+    flip_x: bool,
+) -> Entity {
+    let mut forest_tr = Transform::from_scale(Vec3::splat(2.2));
+    forest_tr.translation = pos;
+    cmd.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: game_assets.forests.clone(),
+        transform: forest_tr,
+        sprite: TextureAtlasSprite {
+            flip_x,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .insert(ZOffset { offset: -100. })
+    .id()
 }
 
-fn spawn_unit_with_class(
+pub fn spawn_unit_with_class(
     cmd: &mut Commands,
     game_assets: &GameAssets,
     resource_assets: &ResourceAssets,
     pos: Vec3,
     class: UnitClass,
     hp_assets: &mut Assets<hp_material::HpMaterial>,
-) {
+) -> Entity {
     let mut carry_sprite_transform =
         Transform::from_translation(Vec3::new(0., 0., 0.0000012));
     carry_sprite_transform.scale = Vec3::splat(0.);
@@ -921,13 +886,13 @@ fn spawn_unit_with_class(
                 .insert(DontSortZ);
         })
         .id();
-    let mut health_comp = Health {
+    // the class components and stats come from the stats table
+    cmd.entity(entity_id).insert(Health {
         current_health: 10.,
         max_health: 10.,
         armor: 0.,
-    };
-    change_class(entity_id, cmd, class, &mut health_comp);
-    cmd.entity(entity_id).insert(health_comp);
+    });
+    entity_id
 }
 
 pub enum LevelState {
@@ -944,16 +909,51 @@ fn spawn_stuff(
     mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
 
     mut bloodrock_amount: ResMut<BloodrockAmount>,
+    mut max_supply: ResMut<MaxSupplyAmount>,
+    level_manager: Res<LevelManager>,
+    game_mode: Res<GameMode>,
+    mut arena: ResMut<Arena>,
+    mut rng: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
 ) {
     if matches!(*level_state, LevelState::NeedToSpawnStuff) {
-        bloodrock_amount.0 = 20;
+        let settings = difficulty.settings();
+        bloodrock_amount.0 = settings.starting_bloodrock(STARTING_BLOODROCK);
+        max_supply.0 = settings.supply_cap(MAX_SUPPLY);
         *level_state = LevelState::SpawnedStuff;
 
-        spawn_bloodrock_node(
-            &mut cmd,
-            &resource_assets,
-            Vec3::new(-100., -100., 0.),
-        );
+        let level = &level_manager.current_level;
+        if *game_mode == GameMode::Campaign && !level.bloodrock_nodes.is_empty()
+        {
+            for pos in level.bloodrock_nodes.iter() {
+                spawn_bloodrock_node(&mut cmd, &resource_assets, *pos);
+            }
+        } else {
+            spawn_bloodrock_node(
+                &mut cmd,
+                &resource_assets,
+                Vec3::new(-100., -100., 0.),
+            );
+        }
+        // endless mode always plays in the default arena
+        let (arena_desc, background, decorations) =
+            if *game_mode == GameMode::Campaign {
+                (level.arena, level.background, level.decorations.clone())
+            } else {
+                Default::default()
+            };
+        *arena = Arena::from(arena_desc);
+        spawn_background(&mut cmd, &game_assets, &background);
+        let decorations = decorations
+            .unwrap_or_else(|| default_decorations(&mut rng.cosmetic));
+        for decoration in decorations.iter() {
+            spawn_forest(
+                &mut cmd,
+                &game_assets,
+                Vec2::from(decoration.position).extend(0.),
+                decoration.flip_x,
+            );
+        }
         cmd.spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.player_sprite.clone(),
             transform: Transform::from_scale(Vec3::splat(0.5)),
@@ -1035,7 +1035,7 @@ impl Plugin for GamePlugin {
         app.insert_resource(GameAssets::default())
             .insert_resource(LevelState::NeedToSpawnStuff)
             .insert_resource(ResourceAssets::default())
-            .insert_resource(MaxSupplyAmount(15))
+            .insert_resource(MaxSupplyAmount(MAX_SUPPLY))
             .insert_resource(BloodrockAmount(25))
             .add_startup_system(setup_game)
             .add_system_to_stage(CoreStage::PostUpdate, z_sorter_system)
@@ -0,0 +1,114 @@
+use bevy::{
+    asset::AssetPlugin, hierarchy::HierarchyPlugin, input::InputPlugin,
+    log::LogPlugin, prelude::*, transform::TransformPlugin,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    add_gameplay_plugins, audio, difficulty,
+    enemy_logic::LevelManager,
+    replay::{Replay, ReplayState, ReplaySummary},
+    ui, FixedTimeStep, SceneState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelOutcome {
+    Win,
+    Lose,
+    /// The level did not finish in the given number of ticks
+    Timeout,
+    /// The level asset could not be loaded, check the logs
+    LoadFailed,
+}
+
+/// Game simulation without a window, rendering, egui or audio
+///
+/// `GameTime` advances by `fixed_delta` on every `App::update`, no matter how
+/// long the update took, so runs do not depend on the speed of the machine.
+pub fn headless_app(fixed_delta: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(AssetPlugin)
+        // sprites and meshes are still spawned, they are just never drawn
+        .add_asset::<Image>()
+        .add_asset::<TextureAtlas>()
+        .add_asset::<Mesh>()
+        // resources and events otherwise provided by the windowed plugins
+        .insert_resource(ui::EndGameManager::default())
+        // normal, runs must not depend on the menu selection; replays still
+        // play back with the (clamped) settings of their header
+        .insert_resource(difficulty::Difficulty::default())
+        .insert_resource(audio::AudioAssets::default())
+        .add_event::<audio::PlayAudioEventPositional>()
+        .add_event::<CursorMoved>()
+        .insert_resource(FixedTimeStep(fixed_delta));
+    add_gameplay_plugins(&mut app);
+    app
+}
+
+/// Loads the level at `level_path` (relative to the assets folder) and runs
+/// it until it is won or lost, or until `max_ticks` in-game updates passed
+pub fn run_level(
+    app: &mut App,
+    level_path: &str,
+    max_ticks: usize,
+) -> LevelOutcome {
+    // run the startup systems first, they select the first campaign level
+    app.update();
+
+    let handle = app.world.resource::<AssetServer>().load(level_path);
+    app.world.resource_mut::<LevelManager>().level_handle = handle;
+    app.world
+        .resource_mut::<State<SceneState>>()
+        .set(SceneState::LoadingLevel)
+        .unwrap_or_default();
+
+    let mut ticks = 0;
+    while ticks < max_ticks {
+        app.update();
+
+        match app.world.resource::<State<SceneState>>().current() {
+            SceneState::InGame => ticks += 1,
+            SceneState::MainMenu => return LevelOutcome::LoadFailed,
+            _ => {}
+        }
+        match app.world.resource::<ui::EndGameManager>().state {
+            ui::EndGameState::Win => return LevelOutcome::Win,
+            ui::EndGameState::Lose => return LevelOutcome::Lose,
+            ui::EndGameState::NotEndGame => {}
+        }
+    }
+    LevelOutcome::Timeout
+}
+
+/// Plays `replay` back and checks that it ends the same way it did when it
+/// was recorded
+pub fn run_replay(
+    app: &mut App,
+    replay: Replay,
+) -> Result<ReplaySummary, String> {
+    app.insert_resource(ReplayState::play(replay));
+
+    let mut left_menu = false;
+    loop {
+        app.update();
+
+        match app.world.resource::<State<SceneState>>().current() {
+            SceneState::MainMenu if left_menu => {
+                return Err("the level of the replay failed to load".into())
+            }
+            SceneState::MainMenu => {}
+            _ => left_menu = true,
+        }
+        if let Some(result) =
+            app.world.resource::<ReplayState>().playback_result.clone()
+        {
+            return result;
+        }
+    }
+}
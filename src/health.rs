@@ -5,16 +5,21 @@ use crate::{
     audio::{AudioAssets, PlayAudioEventPositional},
     combat::{AttackState, CombatComponent},
     easing::Easing,
-    game::{spawn_bloodrock_node, BloodrockAmount, GameAssets, ResourceAssets},
+    elite::{self, Elite},
+    enemy_logic::{EnemyId, EnemyKind},
+    game::{BloodrockAmount, GameAssets},
     interaction::{Hovered, Selected},
+    loot::{LootDropped, LootOnDeath},
     particles,
-    worker_logic::HealerComponent,
+    raider::Raider,
+    rng::GameRng,
+    worker_logic::{HealerComponent, UnitFollowPlayer},
 };
 use bevy::prelude::*;
-use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Serialize, Deserialize)]
 pub struct Health {
     pub max_health: f32,
     pub current_health: f32,
@@ -25,28 +30,52 @@ pub struct HealthChangedEvent {
     pub target: Entity,
     pub amount: f32,
     pub piercing: f32,
+    /// Whoever dealt the damage or healed, if it was a unit
+    pub source: Option<Entity>,
 }
-#[derive(Component)]
-pub struct SpawnResourceNodeOnDeath {
-    pub chance: f32,
-}
-
 pub struct DestroyEntity(pub Entity);
 
+/// A unit ran out of health, sent right before it gets despawned
+pub struct UnitDiedEvent {
+    pub position: Vec3,
+    /// One of the player's units
+    pub ally: bool,
+    pub enemy_kind: Option<EnemyId>,
+}
+
 fn destroyer_system(
     mut cmd: Commands,
-    resource_assets: Res<ResourceAssets>,
     mut destroy_event_reader: EventReader<DestroyEntity>,
     mut combat_comps: Query<(&mut CombatComponent, Entity)>,
     mut healer_comps: Query<(&mut HealerComponent, Entity)>,
     transforms: Query<&GlobalTransform>,
     local_transforms: Query<&Transform>,
-    spawn_on_death: Query<&SpawnResourceNodeOnDeath>,
+    loot: Query<&LootOnDeath>,
     mut selected: ResMut<Selected>,
     mut hovered: ResMut<Hovered>,
     mut amount_of_bloodrock: ResMut<BloodrockAmount>,
+    mut rng: ResMut<GameRng>,
+    units: Query<(&Health, Option<&UnitFollowPlayer>, Option<&EnemyKind>)>,
+    bounties: Query<(Option<&Raider>, Option<&Elite>)>,
+    mut died_events: EventWriter<UnitDiedEvent>,
+    mut loot_dropped: EventWriter<LootDropped>,
 ) {
     for event in destroy_event_reader.iter() {
+        // eaten units, emptied nodes and escaped raiders get destroyed too,
+        // they did not die
+        let mut died = false;
+        if let (Ok((health, ally, enemy_kind)), Ok(tr)) =
+            (units.get(event.0), transforms.get(event.0))
+        {
+            if health.current_health <= 0. {
+                died = true;
+                died_events.send(UnitDiedEvent {
+                    position: tr.translation(),
+                    ally: ally.is_some(),
+                    enemy_kind: enemy_kind.map(|enemy| enemy.kind.clone()),
+                });
+            }
+        }
         //Clear out targets
         for (mut combat_comp, combat_entity) in combat_comps.iter_mut() {
             if let Some(e) = combat_comp.target {
@@ -84,16 +113,34 @@ fn destroyer_system(
                 }
             }
         }
-        if let Ok(e) = transforms.get(event.0) {
-            if let Ok(spawn) = spawn_on_death.get(event.0) {
-                amount_of_bloodrock.0 += 1;
-                let mut rng = rand::thread_rng();
-                if rng.gen_range(0.0..100.0) < spawn.chance {
-                    spawn_bloodrock_node(
-                        &mut cmd,
-                        &resource_assets,
-                        e.translation(),
-                    );
+        if died {
+            if let Ok((raider, elite)) = bounties.get(event.0) {
+                // killing a raider gives back what it stole
+                if let Some(raider) = raider {
+                    amount_of_bloodrock.0 += raider.carried;
+                }
+                if let Some(elite) = elite {
+                    amount_of_bloodrock.0 +=
+                        elite.affixes.len() * elite::BLOODROCK_PER_AFFIX;
+                }
+            }
+            if let (Ok(loot), Ok(e)) =
+                (loot.get(event.0), transforms.get(event.0))
+            {
+                let drops = loot.0.roll(&mut rng.gameplay);
+                for (index, drop) in drops.into_iter().enumerate() {
+                    // spread out so the drops do not stack on top
+                    let offset = if index == 0 {
+                        Vec3::ZERO
+                    } else {
+                        Quat::from_rotation_z(index as f32 * 2.4)
+                            * Vec3::X
+                            * 40.
+                    };
+                    loot_dropped.send(LootDropped {
+                        position: e.translation() + offset,
+                        drop,
+                    });
                 }
             }
         }
@@ -212,7 +259,7 @@ fn spawn_healing_particles(
     });
 }
 
-fn spawn_health_particles(
+pub fn spawn_health_particles(
     commands: &mut Commands,
     texture: Handle<TextureAtlas>,
     pos: Vec3,
@@ -274,6 +321,7 @@ impl Plugin for HealthPlugin {
         )
         .add_event::<HealthChangedEvent>()
         .add_event::<DestroyEntity>()
+        .add_event::<UnitDiedEvent>()
         .add_system(health_change_system)
         .add_system(hp_material::update_hp_materials)
         .add_system(hp_material::update_hp_bar_transform)
@@ -4,14 +4,17 @@ use crate::{
     audio::{AudioAssets, PlayAudioEventPositional},
     collision::AABB,
     easing::Easing,
-    game::GameAssets,
-    health::{DestroyEntity, Health, HealthChangedEvent},
-    particles,
-    worker_logic::{
-        change_class, merge_units, CanEatWorker, UnitClass, UnitSize,
+    game::{
+        spawn_unit_with_class, BloodrockAmount, DontSortZ, GameAssets,
+        MaxSupplyAmount, ResourceAssets,
     },
-    ChangeTimeScaleEvent, PlayerCamera, SceneState, Selectable,
-    DEFAULT_TIME_SCALE,
+    get_children_recursive,
+    health::{hp_material, DestroyEntity, Health, HealthChangedEvent},
+    merge_recipes::{ClassId, MergeRecipes, MergedFrom, MergedPart, TierClass},
+    particles,
+    replay::PlayerInput,
+    worker_logic::{CanEatWorker, UnitClass, UnitFollowPlayer, UnitSize},
+    ChangeTimeScaleEvent, SceneState, Selectable, DEFAULT_TIME_SCALE,
 };
 use std::time::Duration;
 
@@ -21,8 +24,34 @@ pub struct Hovered(pub Option<Entity>);
 pub struct Selected(pub Option<Entity>);
 #[derive(Component)]
 pub struct MouseFollow;
+/// Last known world position of the cursor, the input only has it on ticks
+/// it moved
+pub struct CursorPosition(pub Option<Vec2>);
+/// Corner the selection box is dragged from, while the player drags one
+pub struct SelectionBox(pub Option<Vec2>);
+
+/// Unit picked by box selection, it takes the right click orders
+#[derive(Component)]
+pub struct BoxSelected;
+#[derive(Component)]
+struct SelectionMarker;
+#[derive(Component)]
+struct SelectionBoxSprite;
 
-fn window_to_world(
+/// Freshly split off unit, gets the health fraction of the unit it came from
+/// once its components are in place
+#[derive(Component)]
+pub struct Unmerged {
+    health_fraction: f32,
+}
+
+const CURSOR_RAY_HEIGHT: f32 = 1000.;
+/// Boxes smaller than this are clicks on the ground
+const MIN_BOX_SIZE: f32 = 10.;
+const SELECTION_COLOR: Color = Color::rgba(0.3, 1., 0.3, 0.5);
+const SELECTION_BOX_COLOR: Color = Color::rgba(0.3, 1., 0.3, 0.15);
+
+pub(crate) fn window_to_world(
     window_pos: Vec2,
     window: &Window,
     mut cam_transform: GlobalTransform,
@@ -47,75 +76,44 @@ fn window_to_world(
 }
 
 fn mouse_follow_system(
-    mut cursor_pos: Local<Vec2>,
-    mut cur_move: EventReader<CursorMoved>,
-    windows: Res<Windows>,
-    cameras: Query<
-        (&GlobalTransform, &OrthographicProjection),
-        With<PlayerCamera>,
-    >,
+    input: Res<PlayerInput>,
     mut followers: Query<&mut Transform, With<MouseFollow>>,
 ) {
-    for m in cur_move.iter() {
-        let win = windows.get(m.id).expect("window not found");
-
-        *cursor_pos = m.position;
-        for (cam_tr, proj) in cameras.iter() {
-            let cursor_world =
-                window_to_world(*cursor_pos, &win, *cam_tr, proj);
-
-            for mut tr in followers.iter_mut() {
-                tr.translation =
-                    Vec3::new(cursor_world.x, cursor_world.y, tr.translation.z);
-            }
+    if let Some([x, y]) = input.cursor {
+        for mut tr in followers.iter_mut() {
+            tr.translation = Vec3::new(x, y, tr.translation.z);
         }
     }
 }
 
 fn select_worker_system(
-    mut cursor_pos: Local<Vec2>,
-    windows: Res<Windows>,
-    mut cur_move: EventReader<CursorMoved>,
+    input: Res<PlayerInput>,
     workers: Query<(Entity, &AABB), With<Selectable>>,
-    cameras: Query<
-        (&GlobalTransform, &OrthographicProjection),
-        With<PlayerCamera>,
-    >,
     mut hovered: ResMut<Hovered>,
     mut selected: ResMut<Selected>,
-    btn: Res<Input<MouseButton>>,
     mut cmd: Commands,
     mut time_event: EventWriter<ChangeTimeScaleEvent>,
 ) {
-    for m in cur_move.iter() {
-        let win = windows.get(m.id).expect("window not found");
+    if let Some([x, y]) = input.cursor {
+        // the camera looks straight down, so cast the ray from above the
+        // cursor
+        let ray = [Vec3::new(x, y, CURSOR_RAY_HEIGHT), Vec3::NEG_Z];
 
-        *cursor_pos = m.position;
-        for (cam_tr, proj) in cameras.iter() {
-            let cursor_world =
-                window_to_world(*cursor_pos, &win, *cam_tr, proj);
-
-            let cam_pos = cam_tr.translation();
-            let d = cursor_world - cam_pos;
-
-            hovered.0 = None;
-            debug!("Handling hover for pos: {} d: {}", cam_pos, d);
-            for (entity, aabb) in workers.iter() {
-                trace!("Testing aabb: {:?}", aabb);
-                if crate::collision::primitives::ray_aabb(
-                    [cam_pos, d],
-                    [aabb.min, aabb.max],
-                )
+        hovered.0 = None;
+        debug!("Handling hover for pos: {} {}", x, y);
+        for (entity, aabb) in workers.iter() {
+            trace!("Testing aabb: {:?}", aabb);
+            if crate::collision::primitives::ray_aabb(ray, [aabb.min, aabb.max])
                 .is_some()
-                {
-                    trace!("Hovering {:?}", entity);
-                    hovered.0 = Some(entity);
-                    break;
-                }
+            {
+                trace!("Hovering {:?}", entity);
+                hovered.0 = Some(entity);
+                break;
             }
         }
     }
-    if btn.just_pressed(MouseButton::Left) {
+    // shift clicks add to the box selection instead of dragging
+    if input.select && !input.add_to_selection {
         debug!("Select entity: {:?}", hovered.0);
         selected.0 = hovered.0;
         if let Some(e) = selected.0 {
@@ -127,73 +125,248 @@ fn select_worker_system(
     }
 }
 
+/// Drags a box over the ground to select the units inside, shift adds to the
+/// selection and shift clicking a unit toggles it
+pub(crate) fn box_select_system(
+    mut cmd: Commands,
+    input: Res<PlayerInput>,
+    hovered: Res<Hovered>,
+    mut cursor: ResMut<CursorPosition>,
+    mut selection_box: ResMut<SelectionBox>,
+    units: Query<
+        (Entity, &GlobalTransform, Option<&BoxSelected>),
+        (With<Selectable>, With<UnitFollowPlayer>),
+    >,
+    mut box_sprites: Query<
+        (Entity, &mut Transform, &mut Sprite),
+        With<SelectionBoxSprite>,
+    >,
+) {
+    if let Some([x, y]) = input.cursor {
+        cursor.0 = Some(Vec2::new(x, y));
+    }
+    let cursor = match cursor.0 {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    if input.select {
+        match hovered.0 {
+            Some(entity) if input.add_to_selection => {
+                if let Ok((_, _, box_selected)) = units.get(entity) {
+                    match box_selected {
+                        Some(_) => cmd.entity(entity).remove::<BoxSelected>(),
+                        None => cmd.entity(entity).insert(BoxSelected),
+                    };
+                }
+            }
+            Some(_) => {}
+            None => {
+                selection_box.0 = Some(cursor);
+                cmd.spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: SELECTION_BOX_COLOR,
+                        custom_size: Some(Vec2::ZERO),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(
+                        cursor.extend(CURSOR_RAY_HEIGHT / 2.),
+                    ),
+                    ..Default::default()
+                })
+                .insert(DontSortZ)
+                .insert(SelectionBoxSprite);
+            }
+        }
+    }
+
+    let start = match selection_box.0 {
+        Some(start) => start,
+        None => return,
+    };
+    for (_, mut tr, mut sprite) in box_sprites.iter_mut() {
+        tr.translation = ((start + cursor) / 2.).extend(tr.translation.z);
+        sprite.custom_size = Some((cursor - start).abs());
+    }
+    if !input.release {
+        return;
+    }
+
+    selection_box.0 = None;
+    for (entity, _, _) in box_sprites.iter() {
+        cmd.entity(entity).despawn_recursive();
+    }
+    let (min, max) = (start.min(cursor), start.max(cursor));
+    let is_click = (max - min).max_element() < MIN_BOX_SIZE;
+    for (entity, tr, box_selected) in units.iter() {
+        let pos = tr.translation().truncate();
+        let inside = !is_click && pos.cmpge(min).all() && pos.cmple(max).all();
+        if inside && box_selected.is_none() {
+            cmd.entity(entity).insert(BoxSelected);
+        } else if !inside && box_selected.is_some() && !input.add_to_selection {
+            cmd.entity(entity).remove::<BoxSelected>();
+        }
+    }
+}
+
+/// Shows which units are box selected with a ring under their feet
+fn selection_marker_system(
+    mut cmd: Commands,
+    game_assets: Res<GameAssets>,
+    added: Query<Entity, Added<BoxSelected>>,
+    removed: RemovedComponents<BoxSelected>,
+    children: Query<&Children>,
+    markers: Query<Entity, With<SelectionMarker>>,
+) {
+    for entity in added.iter() {
+        cmd.entity(entity).with_children(|child| {
+            child
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: game_assets.circle_sprite.clone(),
+                    sprite: TextureAtlasSprite {
+                        color: SELECTION_COLOR,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(Vec3::new(
+                        0., -40., -0.000001,
+                    ))
+                    .with_scale(Vec3::new(1.6, 0.6, 1.)),
+                    ..Default::default()
+                })
+                .insert(DontSortZ)
+                .insert(SelectionMarker);
+        });
+    }
+    for entity in removed.iter() {
+        get_children_recursive(entity, &children, &mut |child| {
+            if markers.get(child).is_ok() {
+                cmd.entity(child).despawn_recursive();
+            }
+        });
+    }
+}
+
 fn deselect_on_mouse_up(
-    btn: Res<Input<MouseButton>>,
+    input: Res<PlayerInput>,
     mut selected: ResMut<Selected>,
     mut hovered: ResMut<Hovered>,
     mut cmd: Commands,
-    mut eater: Query<(&CanEatWorker, &mut Health, &GlobalTransform, Entity)>,
-    mut worker_stats: Query<(&mut Transform, &mut UnitClass, &mut UnitSize)>,
+    eater: Query<(&CanEatWorker, &GlobalTransform, Entity)>,
+    mut worker_stats: Query<(
+        &mut Transform,
+        &mut UnitClass,
+        &mut UnitSize,
+        Option<&TierClass>,
+        Option<&MergedFrom>,
+    )>,
     mut time_event: EventWriter<ChangeTimeScaleEvent>,
     mut destroyer_event: EventWriter<DestroyEntity>,
     mut heal_event: EventWriter<HealthChangedEvent>,
     game_assets: Res<GameAssets>,
     audio_assets: Res<AudioAssets>,
     mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+    recipes: Res<MergeRecipes>,
+    mut bloodrock: ResMut<BloodrockAmount>,
 ) {
-    if btn.just_released(MouseButton::Left) {
+    if input.release {
         if let Some(e) = selected.0.take() {
             time_event.send(ChangeTimeScaleEvent {
                 new_time_scale: DEFAULT_TIME_SCALE,
             });
             cmd.entity(e).remove::<MouseFollow>();
 
-            for (eats, mut health, global_tr, eater_entity) in eater.iter_mut()
-            {
+            for (eats, global_tr, eater_entity) in eater.iter() {
                 if let Some(entity_to_eat) = eats.entity_to_eat {
-                    let mut prey_size: f32 = 0.;
-                    let mut prey_class = UnitClass::Worker;
-                    let mut prey_unit_size = UnitSize::Small;
-                    if let Ok((prey_tr, prey_cl, prey_unit)) =
-                        worker_stats.get_mut(e)
-                    {
-                        prey_size = prey_tr.scale.x;
-                        prey_class = prey_cl.clone();
-                        prey_unit_size = prey_unit.clone();
-                    }
-                    if prey_size != 0. {
-                        if let Ok((mut tr, mut eater_class, mut eater_size)) =
-                            worker_stats.get_mut(eater_entity)
-                        {
-                            tr.scale += prey_size / 10.;
-                            let (new_class, new_size) = merge_units(
-                                (*eater_class, *eater_size),
-                                (prey_class, prey_unit_size),
-                            );
-                            send_audio_event.send(PlayAudioEventPositional {
-                                sound: audio_assets.eating.clone(),
-                                position: tr.translation,
-                            });
-                            if *eater_class != new_class {
-                                *eater_class = new_class;
-                                change_class(
-                                    eater_entity,
-                                    &mut cmd,
-                                    new_class,
-                                    &mut health,
-                                );
+                    selected.0 = None;
+                    hovered.0 = None;
+                    let prey = worker_stats.get(e).ok().map(
+                        |(
+                            prey_tr,
+                            prey_class,
+                            prey_size,
+                            prey_tier,
+                            prey_from,
+                        )| {
+                            MergedPart {
+                                class: ClassId::of(*prey_class, prey_tier),
+                                size: *prey_size,
+                                scale: prey_tr.scale.x,
+                                merged_from: prey_from.cloned(),
                             }
-                            heal_event.send(HealthChangedEvent {
-                                target: eater_entity,
-                                amount: 2.,
-                                piercing: 0.,
-                            });
-                            *eater_size = new_size;
+                        },
+                    );
+                    let prey = match prey {
+                        Some(prey) => prey,
+                        None => return,
+                    };
+                    let (
+                        mut tr,
+                        mut eater_class,
+                        mut eater_size,
+                        eater_tier,
+                        eater_from,
+                    ) = match worker_stats.get_mut(eater_entity) {
+                        Ok(eater) => eater,
+                        Err(_) => return,
+                    };
+                    let eater_id = ClassId::of(*eater_class, eater_tier);
+                    let recipe = recipes.find(
+                        (&eater_id, *eater_size),
+                        (&prey.class, prey.size),
+                    );
+                    // the prey just drops back down without a recipe
+                    let recipe = match recipe {
+                        Some(recipe) if recipe.cost <= bloodrock.0 => recipe,
+                        _ => {
+                            info!(
+                                "{} can not merge with {}",
+                                eater_id, prey.class
+                            );
+                            return;
                         }
+                    };
+                    let (new_class, new_tier) =
+                        match recipes.resolve(&recipe.output.class) {
+                            Some(output) => output,
+                            None => return,
+                        };
+                    bloodrock.0 -= recipe.cost;
+                    let prey_scale = prey.scale;
+                    // remembered, so splitting it gives back the same units
+                    let merged_from = MergedFrom {
+                        parts: vec![
+                            MergedPart {
+                                class: eater_id,
+                                size: *eater_size,
+                                scale: tr.scale.x,
+                                merged_from: eater_from.cloned(),
+                            },
+                            prey,
+                        ],
+                        unmerge_cost: recipe.unmerge_cost,
+                    };
+                    cmd.entity(eater_entity).insert(merged_from);
+                    tr.scale += prey_scale / 10.;
+                    send_audio_event.send(PlayAudioEventPositional {
+                        sound: audio_assets.eating.clone(),
+                        position: tr.translation,
+                    });
+
+                    if *eater_class != new_class {
+                        *eater_class = new_class;
                     }
+                    match new_tier {
+                        Some(tier) => cmd.entity(eater_entity).insert(tier),
+                        None => cmd.entity(eater_entity).remove::<TierClass>(),
+                    };
+                    heal_event.send(HealthChangedEvent {
+                        target: eater_entity,
+                        amount: 2.,
+                        piercing: 0.,
+                        source: None,
+                    });
+                    *eater_size = recipe.output.size.unwrap_or(*eater_size);
 
-                    selected.0 = None;
-                    hovered.0 = None;
                     spawn_eating_particles(
                         &mut cmd,
                         &game_assets,
@@ -210,6 +383,146 @@ fn deselect_on_mouse_up(
     }
 }
 
+/// Splits the hovered unit back into the units it was merged from, units
+/// without a record of that split into the inputs of a recipe of their size
+fn unmerge_system(
+    input: Res<PlayerInput>,
+    hovered: Res<Hovered>,
+    selected: Res<Selected>,
+    recipes: Res<MergeRecipes>,
+    mut bloodrock: ResMut<BloodrockAmount>,
+    max_supply: Res<MaxSupplyAmount>,
+    units: Query<
+        (
+            &Transform,
+            &UnitClass,
+            &UnitSize,
+            Option<&TierClass>,
+            &Health,
+            Option<&MergedFrom>,
+        ),
+        With<UnitFollowPlayer>,
+    >,
+    mut cmd: Commands,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
+    mut destroyer_event: EventWriter<DestroyEntity>,
+    audio_assets: Res<AudioAssets>,
+    mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+) {
+    // not while it is getting dragged around
+    if !input.unmerge || selected.0.is_some() {
+        return;
+    }
+    let entity = match hovered.0 {
+        Some(entity) => entity,
+        None => return,
+    };
+    let (tr, class, size, tier_class, health, merged_from) =
+        match units.get(entity) {
+            Ok(unit) => unit,
+            Err(_) => return,
+        };
+    let class_id = ClassId::of(*class, tier_class);
+    let merged_from = match merged_from {
+        Some(merged_from) => Some(merged_from.clone()),
+        // recipes for any size would hand out free size upgrades
+        None => recipes
+            .reverse(&class_id, *size)
+            .filter(|recipe| {
+                recipe.inputs.iter().all(|part| part.size.is_some())
+            })
+            .map(|recipe| MergedFrom {
+                parts: recipe
+                    .inputs
+                    .iter()
+                    .map(|part| MergedPart {
+                        class: part.class.clone(),
+                        size: part.size.unwrap_or(*size),
+                        scale: 1.,
+                        merged_from: None,
+                    })
+                    .collect(),
+                unmerge_cost: recipe.unmerge_cost,
+            }),
+    };
+    let merged_from = match merged_from {
+        Some(merged_from) => merged_from,
+        None => {
+            info!("{} can not be split", class_id);
+            return;
+        }
+    };
+    // one unit turns into two
+    if bloodrock.0 < merged_from.unmerge_cost
+        || units.iter().len() >= max_supply.0
+    {
+        return;
+    }
+    let parts = merged_from
+        .parts
+        .into_iter()
+        .map(|part| {
+            recipes
+                .resolve(&part.class)
+                .map(|(class, tier)| (class, tier, part))
+        })
+        .collect::<Option<Vec<_>>>();
+    let parts = match parts {
+        Some(parts) => parts,
+        None => return,
+    };
+    bloodrock.0 -= merged_from.unmerge_cost;
+
+    let health_fraction = health.current_health / health.max_health;
+    for (index, (class, tier, part)) in parts.into_iter().enumerate() {
+        let pos = tr.translation + Vec3::X * (index as f32 * 80. - 40.);
+        let entity = spawn_unit_with_class(
+            &mut cmd,
+            &game_assets,
+            &resource_assets,
+            pos,
+            class,
+            &mut *hp_assets,
+        );
+        cmd.entity(entity)
+            .insert(
+                Transform::from_translation(pos)
+                    .with_scale(Vec3::splat(part.scale)),
+            )
+            .insert(part.size)
+            .insert(Unmerged { health_fraction });
+        if let Some(tier) = tier {
+            cmd.entity(entity).insert(tier);
+        }
+        if let Some(merged_from) = part.merged_from {
+            cmd.entity(entity).insert(merged_from);
+        }
+    }
+    info!("split {:?} into the inputs of its recipe", entity);
+    destroyer_event.send(DestroyEntity(entity));
+    spawn_eating_particles(
+        &mut cmd,
+        &game_assets,
+        tr.translation + Vec3::new(0., 40., 1.),
+    );
+    send_audio_event.send(PlayAudioEventPositional {
+        sound: audio_assets.spawning_unit.clone(),
+        position: tr.translation,
+    });
+}
+
+fn unmerged_health_system(
+    mut cmd: Commands,
+    mut parts: Query<(Entity, &Unmerged, &mut Health)>,
+) {
+    for (entity, unmerged, mut health) in parts.iter_mut() {
+        health.current_health = health.max_health * unmerged.health_fraction;
+        cmd.entity(entity).remove::<Unmerged>();
+    }
+}
+
 fn spawn_eating_particles(
     cmd: &mut Commands,
     game_assets: &GameAssets,
@@ -262,9 +575,16 @@ fn spawn_eating_particles(
     });
 }
 
-fn setup(mut hovered: ResMut<Hovered>, mut selected: ResMut<Selected>) {
+fn setup(
+    mut hovered: ResMut<Hovered>,
+    mut selected: ResMut<Selected>,
+    mut cursor: ResMut<CursorPosition>,
+    mut selection_box: ResMut<SelectionBox>,
+) {
     hovered.0 = None;
     selected.0 = None;
+    cursor.0 = None;
+    selection_box.0 = None;
 }
 
 impl Plugin for InteractionPlugin {
@@ -281,7 +601,11 @@ impl Plugin for InteractionPlugin {
         .add_system_set(
             SystemSet::on_update(SceneState::InGame)
                 .with_system(select_worker_system)
-                .with_system(mouse_follow_system),
+                .with_system(box_select_system.after(select_worker_system))
+                .with_system(selection_marker_system)
+                .with_system(mouse_follow_system)
+                .with_system(unmerge_system)
+                .with_system(unmerged_health_system),
         )
         .add_system_set(
             SystemSet::on_update(SceneState::InGame)
@@ -289,6 +613,8 @@ impl Plugin for InteractionPlugin {
                 .with_system(deselect_on_mouse_up),
         )
         .insert_resource(Hovered(None))
-        .insert_resource(Selected(None));
+        .insert_resource(Selected(None))
+        .insert_resource(CursorPosition(None))
+        .insert_resource(SelectionBox(None));
     }
 }
@@ -0,0 +1,531 @@
+use anyhow::{anyhow, bail};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    elite::EliteDescriptor,
+    enemy_archetype::EnemyArchetypes,
+    enemy_logic::{EnemyId, Level, LevelManager, Wave},
+    objectives::{ObjectiveDescriptor, ObjectiveGoal},
+    spawn_formation::{ArenaEdge, Formation},
+};
+
+/// Level description as it is stored in `assets/levels/*.level.json`
+#[derive(Debug, Clone, PartialEq, Serialize, TypeUuid)]
+#[uuid = "6f0c2a43-5d0e-4a8e-9bb4-2f7d3c1e8a61"]
+pub struct LevelAsset {
+    pub name: String,
+    pub waves: Vec<WaveDescriptor>,
+    /// Bloodrock nodes at the start of the level, a single node next to the
+    /// player if empty
+    pub bloodrock_nodes: Vec<[f32; 2]>,
+    pub arena: ArenaDescriptor,
+    pub background: BackgroundDescriptor,
+    /// The default forests around the arena if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decorations: Option<Vec<DecorationDescriptor>>,
+    pub objectives: Vec<ObjectiveDescriptor>,
+}
+
+/// Playable rectangle, enemies outside of it walk back in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaDescriptor {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Default for ArenaDescriptor {
+    fn default() -> Self {
+        ArenaDescriptor {
+            min: [-1000., -650.],
+            max: [700., 650.],
+        }
+    }
+}
+
+/// Grid of background tiles around the origin, `min_tile` and `max_tile` are
+/// inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundDescriptor {
+    pub min_tile: [i32; 2],
+    pub max_tile: [i32; 2],
+    pub scale: f32,
+}
+
+impl Default for BackgroundDescriptor {
+    fn default() -> Self {
+        BackgroundDescriptor {
+            min_tile: [-2, -2],
+            max_tile: [2, 2],
+            scale: 1.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WaveDescriptor {
+    pub spawn_data: Vec<SpawnGroupDescriptor>,
+    /// In seconds, the trigger is only checked after this delay
+    pub time_to_spawn_after_last_wave: f32,
+    pub trigger: WaveTrigger,
+    /// Bloodrock granted for calling the wave before it triggers, the wave
+    /// can not be called early if `None`
+    pub early_call_bonus: Option<usize>,
+    /// Chance of the wave's enemies to spawn as elites
+    pub elites: EliteDescriptor,
+}
+
+/// Condition that spawns a wave, once its delay is over
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WaveTrigger {
+    /// Spawns as soon as the delay is over
+    #[default]
+    Delay,
+    /// Fewer than `count` enemies are alive
+    EnemiesBelow {
+        count: usize,
+    },
+    AllEnemiesDead,
+    /// The player is inside the rectangle between `min` and `max`
+    PlayerInRegion {
+        min: [f32; 2],
+        max: [f32; 2],
+    },
+}
+
+impl WaveTrigger {
+    pub fn is_met(
+        &self,
+        enemies_alive: usize,
+        player_pos: Option<Vec2>,
+    ) -> bool {
+        match *self {
+            WaveTrigger::Delay => true,
+            WaveTrigger::EnemiesBelow { count } => enemies_alive < count,
+            WaveTrigger::AllEnemiesDead => enemies_alive == 0,
+            WaveTrigger::PlayerInRegion { min, max } => {
+                player_pos.map_or(false, |pos| {
+                    pos.cmpge(Vec2::from(min)).all()
+                        && pos.cmple(Vec2::from(max)).all()
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnGroupDescriptor {
+    pub enemies: Vec<EnemyId>,
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub formation: Formation,
+    /// Spawns the group outside of this edge of the arena, it marches in to
+    /// `position` keeping the formation
+    #[serde(default)]
+    pub entry: Option<ArenaEdge>,
+}
+
+/// Forest sprite placed on top of the background
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecorationDescriptor {
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub flip_x: bool,
+}
+
+/// Rows of forests around the default arena
+pub fn default_decorations(rng: &mut impl Rng) -> Vec<DecorationDescriptor> {
+    let mut decorations = Vec::new();
+    let mut push = |x: f32, y: f32| {
+        decorations.push(DecorationDescriptor {
+            position: [x, y],
+            flip_x: rng.gen::<bool>(),
+        })
+    };
+    for bottom in [700., -1000.] {
+        for c in -5..=5 {
+            for r in 0..=5 {
+                push(
+                    c as f32 * 242. * 2.2 + r as f32 * 60.,
+                    bottom + r as f32 * 100.,
+                );
+            }
+        }
+    }
+    for side in [-1500., 1500.] {
+        for i in -1..=0 {
+            for r in -4..=6 {
+                push(side + i as f32 * 550., r as f32 * 100.);
+            }
+        }
+    }
+    decorations
+}
+
+impl LevelAsset {
+    /// Enemy ids are only checked once the archetypes are loaded as well
+    pub fn check_enemies(
+        &self,
+        archetypes: &EnemyArchetypes,
+    ) -> Result<(), anyhow::Error> {
+        for (wave_index, wave) in self.waves.iter().enumerate() {
+            for (group_index, group) in wave.spawn_data.iter().enumerate() {
+                if let Some(id) =
+                    group.enemies.iter().find(|id| !archetypes.knows(id))
+                {
+                    bail!(
+                        "wave {}, spawn group {}: unknown enemy {}",
+                        wave_index,
+                        group_index,
+                        id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_level(&self) -> Level {
+        Level {
+            waves: self
+                .waves
+                .iter()
+                .map(|wave| Wave {
+                    spawn_data: wave.spawn_data.clone(),
+                    time_to_spawn_after_last_wave: Timer::from_seconds(
+                        wave.time_to_spawn_after_last_wave,
+                        false,
+                    ),
+                    trigger: wave.trigger,
+                    early_call_bonus: wave.early_call_bonus,
+                    elites: wave.elites.clone(),
+                })
+                .collect(),
+            current_wave_index: 0,
+            bloodrock_nodes: self
+                .bloodrock_nodes
+                .iter()
+                .map(|pos| Vec3::new(pos[0], pos[1], 0.))
+                .collect(),
+            arena: self.arena,
+            background: self.background,
+            decorations: self.decorations.clone(),
+            objectives: self.objectives.clone(),
+        }
+    }
+}
+
+// Waves and spawn groups are kept as raw values first, so errors can point
+// at the wave that is broken instead of a line/column pair.
+#[derive(Deserialize)]
+struct RawLevel {
+    #[serde(default)]
+    name: String,
+    waves: Vec<serde_json::Value>,
+    #[serde(default)]
+    bloodrock_nodes: Vec<[f32; 2]>,
+    #[serde(default)]
+    arena: ArenaDescriptor,
+    #[serde(default)]
+    background: BackgroundDescriptor,
+    #[serde(default)]
+    decorations: Option<Vec<DecorationDescriptor>>,
+    /// Killing every wave if missing
+    #[serde(default)]
+    objectives: Option<Vec<ObjectiveDescriptor>>,
+}
+
+#[derive(Deserialize)]
+struct RawWave {
+    spawn_data: Vec<serde_json::Value>,
+    time_to_spawn_after_last_wave: f32,
+    #[serde(default)]
+    trigger: WaveTrigger,
+    #[serde(default)]
+    early_call_bonus: Option<usize>,
+    #[serde(default)]
+    elites: EliteDescriptor,
+}
+
+pub fn parse_level(bytes: &[u8]) -> Result<LevelAsset, anyhow::Error> {
+    let raw: RawLevel = serde_json::from_slice(bytes)
+        .map_err(|err| anyhow!("malformed level file: {}", err))?;
+    if raw.waves.is_empty() {
+        bail!("level has no waves");
+    }
+
+    let mut waves = Vec::with_capacity(raw.waves.len());
+    for (wave_index, wave) in raw.waves.into_iter().enumerate() {
+        let wave: RawWave = serde_json::from_value(wave)
+            .map_err(|err| anyhow!("wave {}: {}", wave_index, err))?;
+
+        let delay = wave.time_to_spawn_after_last_wave;
+        if !delay.is_finite() || delay < 0. {
+            bail!(
+                "wave {}: field `time_to_spawn_after_last_wave` must be a \
+                 non-negative number of seconds, got {}",
+                wave_index,
+                delay
+            );
+        }
+
+        if let WaveTrigger::PlayerInRegion { min, max } = wave.trigger {
+            if min[0] > max[0] || min[1] > max[1] {
+                bail!(
+                    "wave {}: trigger region `min` {:?} is not below `max` {:?}",
+                    wave_index,
+                    min,
+                    max
+                );
+            }
+        }
+
+        if !(0.0..=1.0).contains(&wave.elites.chance)
+            || wave.elites.max_affixes == 0
+        {
+            bail!(
+                "wave {}: elite `chance` must be between 0 and 1 and \
+                 `max_affixes` at least 1",
+                wave_index
+            );
+        }
+
+        let mut spawn_data = Vec::with_capacity(wave.spawn_data.len());
+        for (group_index, group) in wave.spawn_data.into_iter().enumerate() {
+            let group: SpawnGroupDescriptor = serde_json::from_value(group)
+                .map_err(|err| {
+                    anyhow!(
+                        "wave {}, spawn group {}: {}",
+                        wave_index,
+                        group_index,
+                        err
+                    )
+                })?;
+            if group.enemies.is_empty() {
+                bail!(
+                    "wave {}, spawn group {}: field `enemies` is empty",
+                    wave_index,
+                    group_index
+                );
+            }
+            spawn_data.push(group);
+        }
+
+        waves.push(WaveDescriptor {
+            spawn_data,
+            time_to_spawn_after_last_wave: delay,
+            trigger: wave.trigger,
+            early_call_bonus: wave.early_call_bonus,
+            elites: wave.elites,
+        });
+    }
+
+    let arena = raw.arena;
+    if arena.min[0] >= arena.max[0] || arena.min[1] >= arena.max[1] {
+        bail!(
+            "arena `min` {:?} is not below `max` {:?}",
+            arena.min,
+            arena.max
+        );
+    }
+    let background = raw.background;
+    if background.min_tile[0] > background.max_tile[0]
+        || background.min_tile[1] > background.max_tile[1]
+        || !background.scale.is_finite()
+        || background.scale <= 0.
+    {
+        bail!(
+            "background needs `min_tile` below `max_tile` and a positive \
+             `scale`, got {:?}",
+            background
+        );
+    }
+
+    let objectives = raw.objectives.unwrap_or_else(|| {
+        vec![ObjectiveDescriptor {
+            goal: ObjectiveGoal::KillAllWaves,
+            secondary: false,
+        }]
+    });
+    if !objectives.iter().any(|objective| {
+        !objective.secondary && !objective.goal.is_constraint()
+    }) {
+        bail!(
+            "level needs a primary objective that can be completed, \
+             `Protect` and `LimitUnitLosses` can only fail"
+        );
+    }
+
+    Ok(LevelAsset {
+        name: raw.name,
+        waves,
+        bloodrock_nodes: raw.bloodrock_nodes,
+        arena,
+        background,
+        decorations: raw.decorations,
+        objectives,
+    })
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level = parse_level(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.json"]
+    }
+}
+
+/// Picks up the level once it finished loading, or got hot reloaded
+fn level_asset_event_system(
+    mut events: EventReader<AssetEvent<LevelAsset>>,
+    levels: Res<Assets<LevelAsset>>,
+    archetypes: Res<EnemyArchetypes>,
+    mut level_manager: ResMut<LevelManager>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } => {
+                if *handle != level_manager.level_handle {
+                    continue;
+                }
+                if let Some(level) = levels.get(handle) {
+                    info!("level loaded with {} waves", level.waves.len());
+                    level_manager.current_level = level.to_level();
+                }
+            }
+            AssetEvent::Modified { handle } => {
+                if *handle != level_manager.level_handle {
+                    continue;
+                }
+                if let Some(level) = levels.get(handle) {
+                    if archetypes.is_loaded() {
+                        if let Err(err) = level.check_enemies(&archetypes) {
+                            error!("Ignoring the reloaded level: {}", err);
+                            continue;
+                        }
+                    }
+                    info!("level reloaded with {} waves", level.waves.len());
+                    // a level in progress keeps the waves it already went through
+                    let wave_index =
+                        level_manager.current_level.current_wave_index;
+                    level_manager.current_level = level.to_level();
+                    level_manager.current_level.current_wave_index =
+                        wave_index.min(level.waves.len());
+                }
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LevelAsset>()
+            .init_asset_loader::<LevelLoader>()
+            .add_system(level_asset_event_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy_archetype::parse_archetypes;
+
+    fn shipped_archetypes() -> EnemyArchetypes {
+        let asset = parse_archetypes(include_bytes!(
+            "../assets/enemies.archetypes.json"
+        ))
+        .unwrap();
+        EnemyArchetypes {
+            enemies: asset.enemies,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shipped_levels_are_valid() {
+        let archetypes = shipped_archetypes();
+        for bytes in [
+            &include_bytes!("../assets/levels/level1.level.json")[..],
+            &include_bytes!("../assets/levels/level2.level.json")[..],
+            &include_bytes!("../assets/levels/level3.level.json")[..],
+        ] {
+            let level = parse_level(bytes).unwrap();
+            level.check_enemies(&archetypes).unwrap();
+        }
+    }
+
+    #[test]
+    fn errors_name_the_broken_wave_and_group() {
+        let err = parse_level(br#"{ "waves": [] }"#).unwrap_err();
+        assert_eq!(err.to_string(), "level has no waves");
+
+        let err = parse_level(
+            br#"{ "waves": [
+                { "time_to_spawn_after_last_wave": 1, "spawn_data": [] },
+                { "time_to_spawn_after_last_wave": -1, "spawn_data": [] }
+            ] }"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("wave 1:"), "{}", err);
+
+        let err = parse_level(
+            br#"{ "waves": [
+                { "time_to_spawn_after_last_wave": 1, "spawn_data": [
+                    { "enemies": ["Thrash"], "position": [0, 0] },
+                    { "enemies": [], "position": [0, 0] }
+                ] }
+            ] }"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with("wave 0, spawn group 1:"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn unknown_enemies_are_rejected() {
+        let level = parse_level(
+            br#"{ "waves": [
+                { "time_to_spawn_after_last_wave": 1, "spawn_data": [
+                    { "enemies": ["Thrash", "BloodrockNode"], "position": [0, 0] }
+                ] },
+                { "time_to_spawn_after_last_wave": 1, "spawn_data": [
+                    { "enemies": ["Thrash"], "position": [0, 0] },
+                    { "enemies": ["Thrsh"], "position": [0, 0] }
+                ] }
+            ] }"#,
+        )
+        .unwrap();
+        let err = level.check_enemies(&shipped_archetypes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wave 1, spawn group 1: unknown enemy Thrsh"
+        );
+    }
+}
@@ -1,21 +1,49 @@
 mod animation;
 mod audio;
+mod boss;
+mod campaign;
 mod collision;
 mod combat;
+mod difficulty;
 mod easing;
+#[cfg(feature = "debug_gui")]
+mod editor;
+mod elite;
+mod endless;
+mod enemy_ai;
+mod enemy_archetype;
 mod enemy_logic;
 mod game;
+mod headless;
 mod health;
 mod interaction;
 mod lerp;
+mod level;
+mod loot;
+mod merge_recipes;
+mod objectives;
+mod orders;
 mod particles;
+mod raider;
+mod replay;
+mod rng;
+mod savegame;
+mod spawn_formation;
+mod storage;
+mod targeting;
 mod ui;
+mod unit_stats;
 mod worker_logic;
 
 use bevy_egui::EguiPlugin;
 use std::time::Duration;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub use headless::{headless_app, run_level, run_replay, LevelOutcome};
+pub use replay::{Replay, ReplayState};
+pub use rng::GameRng;
 
 pub const LAUNCHER_TITLE: &str = "Goo vs Goblins";
 
@@ -27,6 +55,18 @@ pub enum SceneState {
     MainMenu,
     InGame,
     Paused,
+    /// Unloads the previous level and waits for the selected one to load
+    LoadingLevel,
+    /// Level editor, only reachable with the `debug_gui` feature
+    Editor,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Fixed list of waves from the selected level
+    Campaign,
+    /// Enemy spawners getting harder over time, until the player dies
+    Endless,
 }
 
 pub struct GameTime {
@@ -67,12 +107,23 @@ pub struct ChangeTimeScaleEvent {
 
 const DEFAULT_TIME_SCALE: f32 = 1.0;
 
+const GAME_TIME_UPDATE: &str = "game-time-update";
+const UNLOAD_LEVEL: &str = "unload-level";
+
+/// When present, `GameTime` advances by this much every frame instead of
+/// following the wall clock
+pub struct FixedTimeStep(pub Duration);
+
 fn game_time_update(
     time: Res<Time>,
+    fixed_step: Option<Res<FixedTimeStep>>,
     mut game_time: ResMut<GameTime>,
     mut change_events: EventReader<ChangeTimeScaleEvent>,
 ) {
-    let delta = time.delta();
+    let delta = match fixed_step {
+        Some(step) => step.0,
+        None => time.delta(),
+    };
     game_time.real_delta = delta;
 
     for event in change_events.iter() {
@@ -126,13 +177,68 @@ fn unload_level(
     query: Query<Entity, (Without<DontDestroyBetweenLevels>, Without<Parent>)>,
     mut level_state: ResMut<game::LevelState>,
     mut level_manager: ResMut<enemy_logic::LevelManager>,
+    levels: Res<Assets<level::LevelAsset>>,
 ) {
     info!("unloading level!");
     for entity in query.iter() {
         cmd.entity(entity).despawn_recursive();
     }
     *level_state = game::LevelState::NeedToSpawnStuff;
-    level_manager.current_level = enemy_logic::get_test_level();
+    // if the asset is still loading the level gets set once it arrives, the
+    // waves of the previous level must not start in the meantime
+    level_manager.current_level = levels
+        .get(&level_manager.level_handle)
+        .map(|level| level.to_level())
+        .unwrap_or_default();
+}
+
+/// Plugins and state shared by the windowed and the headless app
+fn add_gameplay_plugins(app: &mut App) {
+    app.add_plugin(collision::CollisionPlugin)
+        .add_plugin(interaction::InteractionPlugin)
+        .add_plugin(replay::ReplayPlugin)
+        .add_plugin(rng::RngPlugin)
+        .add_plugin(savegame::SaveGamePlugin)
+        .add_plugin(level::LevelPlugin)
+        .add_plugin(campaign::CampaignPlugin)
+        .add_plugin(endless::EndlessPlugin)
+        .add_plugin(game::GamePlugin)
+        .add_plugin(worker_logic::WorkerLogicPlugin)
+        .add_plugin(enemy_archetype::EnemyArchetypePlugin)
+        .add_plugin(merge_recipes::MergeRecipesPlugin)
+        .add_plugin(unit_stats::UnitStatsPlugin)
+        .add_plugin(enemy_logic::EnemyLogicPlugin)
+        .add_plugin(enemy_ai::EnemyAiPlugin)
+        .add_plugin(targeting::TargetingPlugin)
+        .add_plugin(orders::OrdersPlugin)
+        .add_plugin(raider::RaiderPlugin)
+        .add_plugin(elite::ElitePlugin)
+        .add_plugin(loot::LootPlugin)
+        .add_plugin(boss::BossPlugin)
+        .add_plugin(spawn_formation::SpawnFormationPlugin)
+        .add_plugin(objectives::ObjectivesPlugin)
+        .add_plugin(health::HealthPlugin)
+        .add_plugin(combat::CombatPlugin)
+        .add_plugin(animation::AnimationsPlugin)
+        .add_plugin(particles::ParticlePlugin)
+        .add_state(SceneState::MainMenu)
+        .add_system_set(
+            SystemSet::on_enter(SceneState::MainMenu)
+                .with_system(unload_level.label(UNLOAD_LEVEL)),
+        )
+        .add_system_set(
+            SystemSet::on_enter(SceneState::LoadingLevel)
+                .with_system(unload_level.label(UNLOAD_LEVEL)),
+        )
+        // before anything reads the input or the time of this tick
+        .add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(game_time_update.label(GAME_TIME_UPDATE)),
+        )
+        .insert_resource(GameTime::default())
+        .insert_resource(GameMode::Campaign)
+        .add_event::<ChangeTimeScaleEvent>();
 }
 
 pub fn app() -> App {
@@ -143,30 +249,21 @@ pub fn app() -> App {
         fit_canvas_to_parent: true,
         ..Default::default()
     })
+    // lets the data files get tweaked while the game runs
+    .insert_resource(bevy::asset::AssetServerSettings {
+        watch_for_changes: cfg!(debug_assertions),
+        ..Default::default()
+    })
     .add_plugins(DefaultPlugins)
-    .add_plugin(collision::CollisionPlugin)
-    .add_plugin(interaction::InteractionPlugin)
-    .add_plugin(game::GamePlugin)
-    .add_plugin(worker_logic::WorkerLogicPlugin)
-    .add_plugin(enemy_logic::EnemyLogicPlugin)
-    .add_plugin(health::HealthPlugin)
-    .add_plugin(combat::CombatPlugin)
-    .add_plugin(animation::AnimationsPlugin)
-    .add_plugin(particles::ParticlePlugin)
     .add_plugin(ui::UIPlugin)
     .add_plugin(EguiPlugin)
     .add_plugin(bevy_kira_audio::AudioPlugin)
     .add_plugin(audio::AudioPlugin)
-    .add_state(SceneState::MainMenu)
-    .add_startup_system(setup_player_camera)
-    .add_system_set(
-        SystemSet::on_enter(SceneState::MainMenu).with_system(unload_level),
-    )
-    .add_system_set(
-        SystemSet::on_update(SceneState::InGame).with_system(game_time_update),
-    )
-    .insert_resource(GameTime::default())
-    .add_event::<ChangeTimeScaleEvent>();
+    .add_plugin(difficulty::DifficultyPlugin)
+    .add_startup_system(setup_player_camera);
+    add_gameplay_plugins(&mut app);
+    #[cfg(feature = "debug_gui")]
+    app.add_plugin(editor::EditorPlugin);
 
     app
 }
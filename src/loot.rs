@@ -0,0 +1,294 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::{AudioAssets, PlayAudioEventPositional},
+    game::{
+        spawn_bloodrock_node, BloodrockAmount, GameAssets, PlayerController,
+        ResourceAssets,
+    },
+    worker_logic::UnitFollowPlayer,
+    GameTime, SceneState,
+};
+
+/// How close the player or one of their units has to get to collect a drop
+const PICKUP_RANGE: f32 = 70.;
+/// Seconds before an uncollected drop disappears
+const PICKUP_LIFETIME: f32 = 20.;
+const BUFF_COLOR: Color = Color::rgb(0.3, 0.9, 1.);
+const ITEM_COLOR: Color = Color::rgb(1., 0.8, 0.3);
+
+/// Stats of the player's units that buffs and items boost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuffStat {
+    Damage,
+    AttackSpeed,
+}
+
+impl BuffStat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuffStat::Damage => "Damage",
+            BuffStat::AttackSpeed => "Attack speed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum LootDrop {
+    /// Lets tables roll empty
+    Nothing,
+    Bloodrock {
+        amount: usize,
+    },
+    /// Leaves a bloodrock node behind
+    ResourceNode,
+    /// Boosts all of the player's units for a while
+    Buff {
+        stat: BuffStat,
+        multiplier: f32,
+        /// Seconds
+        duration: f32,
+    },
+    /// Boosts all of the player's units for the rest of the level
+    Item {
+        name: String,
+        stat: BuffStat,
+        multiplier: f32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LootEntry {
+    /// Relative to the other entries of the table
+    pub weight: f32,
+    #[serde(flatten)]
+    pub drop: LootDrop,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LootTable {
+    /// Entries picked on death, the same one can be picked more than once
+    pub rolls: usize,
+    pub entries: Vec<LootEntry>,
+}
+
+/// A single bloodrock, what every enemy used to give
+impl Default for LootTable {
+    fn default() -> Self {
+        LootTable {
+            rolls: 1,
+            entries: vec![LootEntry {
+                weight: 1.,
+                drop: LootDrop::Bloodrock { amount: 1 },
+            }],
+        }
+    }
+}
+
+impl LootTable {
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<LootDrop> {
+        let index = match WeightedIndex::new(
+            self.entries.iter().map(|entry| entry.weight),
+        ) {
+            Ok(index) => index,
+            Err(_) => return Vec::new(),
+        };
+        (0..self.rolls)
+            .map(|_| &self.entries[index.sample(rng)].drop)
+            .filter(|drop| **drop != LootDrop::Nothing)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Component)]
+pub struct LootOnDeath(pub LootTable);
+
+/// Something got dropped at `position`, sent for every rolled drop
+pub struct LootDropped {
+    pub position: Vec3,
+    pub drop: LootDrop,
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    drop: LootDrop,
+    lifetime: Timer,
+}
+
+pub struct ActiveBuff {
+    /// Shown in the UI, the name of the item for items
+    pub name: String,
+    pub stat: BuffStat,
+    pub multiplier: f32,
+    /// `None` for items, they last until the level ends
+    pub timer: Option<Timer>,
+}
+
+/// Buffs and items the player collected this level
+#[derive(Default)]
+pub struct LootBuffs {
+    pub active: Vec<ActiveBuff>,
+}
+
+impl LootBuffs {
+    /// Every active buff of `stat` multiplied together
+    pub fn multiplier(&self, stat: BuffStat) -> f32 {
+        self.active
+            .iter()
+            .filter(|buff| buff.stat == stat)
+            .map(|buff| buff.multiplier)
+            .product()
+    }
+}
+
+fn spawn_loot_system(
+    mut cmd: Commands,
+    mut loot_dropped: EventReader<LootDropped>,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    audio_assets: Res<AudioAssets>,
+    mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+) {
+    for event in loot_dropped.iter() {
+        let (texture_atlas, color, scale) = match &event.drop {
+            LootDrop::Nothing => continue,
+            LootDrop::ResourceNode => {
+                spawn_bloodrock_node(
+                    &mut cmd,
+                    &resource_assets,
+                    event.position,
+                );
+                continue;
+            }
+            LootDrop::Bloodrock { .. } => {
+                (resource_assets.bloodrock.clone(), Color::WHITE, 0.8)
+            }
+            LootDrop::Buff { .. } => {
+                (game_assets.circle_sprite.clone(), BUFF_COLOR, 0.4)
+            }
+            LootDrop::Item { .. } => {
+                send_audio_event.send(PlayAudioEventPositional {
+                    sound: audio_assets.rare_loot.clone(),
+                    position: event.position,
+                });
+                (game_assets.circle_sprite.clone(), ITEM_COLOR, 0.6)
+            }
+        };
+        cmd.spawn_bundle(SpriteSheetBundle {
+            texture_atlas,
+            sprite: TextureAtlasSprite {
+                color,
+                ..Default::default()
+            },
+            transform: Transform::from_translation(event.position)
+                .with_scale(Vec3::splat(scale)),
+            ..Default::default()
+        })
+        .insert(Pickup {
+            drop: event.drop.clone(),
+            lifetime: Timer::from_seconds(PICKUP_LIFETIME, false),
+        });
+    }
+}
+
+fn pickup_system(
+    mut cmd: Commands,
+    time: Res<GameTime>,
+    mut pickups: Query<(Entity, &mut Pickup, &GlobalTransform)>,
+    collectors: Query<
+        &GlobalTransform,
+        Or<(With<PlayerController>, With<UnitFollowPlayer>)>,
+    >,
+    mut bloodrock: ResMut<BloodrockAmount>,
+    mut buffs: ResMut<LootBuffs>,
+    audio_assets: Res<AudioAssets>,
+    mut send_audio_event: EventWriter<PlayAudioEventPositional>,
+) {
+    for (entity, mut pickup, tr) in pickups.iter_mut() {
+        let pos = tr.translation();
+        pickup.lifetime.tick(time.delta());
+        if pickup.lifetime.finished() {
+            cmd.entity(entity).despawn_recursive();
+            continue;
+        }
+        let collected = collectors.iter().any(|collector| {
+            (collector.translation() - pos).truncate().length() <= PICKUP_RANGE
+        });
+        if !collected {
+            continue;
+        }
+
+        match &pickup.drop {
+            LootDrop::Bloodrock { amount } => bloodrock.0 += amount,
+            LootDrop::Buff {
+                stat,
+                multiplier,
+                duration,
+            } => buffs.active.push(ActiveBuff {
+                name: stat.name().to_string(),
+                stat: *stat,
+                multiplier: *multiplier,
+                timer: Some(Timer::from_seconds(*duration, false)),
+            }),
+            LootDrop::Item {
+                name,
+                stat,
+                multiplier,
+            } => buffs.active.push(ActiveBuff {
+                name: name.clone(),
+                stat: *stat,
+                multiplier: *multiplier,
+                timer: None,
+            }),
+            LootDrop::Nothing | LootDrop::ResourceNode => {}
+        }
+        send_audio_event.send(PlayAudioEventPositional {
+            sound: audio_assets.loot_pickup.clone(),
+            position: pos,
+        });
+        cmd.entity(entity).despawn_recursive();
+    }
+}
+
+fn loot_buffs_system(time: Res<GameTime>, mut buffs: ResMut<LootBuffs>) {
+    for buff in buffs.active.iter_mut() {
+        if let Some(timer) = &mut buff.timer {
+            timer.tick(time.delta());
+        }
+    }
+    buffs
+        .active
+        .retain(|buff| !buff.timer.as_ref().map_or(false, Timer::finished));
+}
+
+fn reset_loot_buffs_system(mut buffs: ResMut<LootBuffs>) {
+    buffs.active.clear();
+}
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LootBuffs>()
+            .add_event::<LootDropped>()
+            .add_system_set(
+                SystemSet::on_update(SceneState::InGame)
+                    .with_system(spawn_loot_system)
+                    .with_system(pickup_system)
+                    .with_system(loot_buffs_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::MainMenu)
+                    .with_system(reset_loot_buffs_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::LoadingLevel)
+                    .with_system(reset_loot_buffs_system),
+            );
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::{anyhow, bail};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::worker_logic::{UnitClass, UnitSize};
+
+const RECIPES_FILE: &str = "units.recipes.json";
+
+/// Name of a unit class, either one of [`UnitClass`] or a tier class of the
+/// recipe file
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ClassId(pub String);
+
+impl From<&str> for ClassId {
+    fn from(id: &str) -> Self {
+        ClassId(id.to_string())
+    }
+}
+
+impl std::fmt::Display for ClassId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ClassId {
+    /// The class a unit merges as
+    pub fn of(class: UnitClass, tier_class: Option<&TierClass>) -> Self {
+        match tier_class {
+            Some(tier_class) => tier_class.id.clone(),
+            None => ClassId::from(class.name()),
+        }
+    }
+}
+
+/// A stronger class only reachable by merging, it fights and looks like its
+/// base class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TierClassDescriptor {
+    pub base: UnitClass,
+    /// 2 or above, the basic classes are tier 1
+    pub tier: u8,
+    /// Tints the sprite
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+    /// Multiplies the damage of the base class
+    #[serde(default = "default_multiplier")]
+    pub damage: f32,
+    /// Multiplies the health of the base class
+    #[serde(default = "default_multiplier")]
+    pub health: f32,
+}
+
+fn default_multiplier() -> f32 {
+    1.
+}
+
+/// Units of a tier class, their `UnitClass` is the base class
+#[derive(Component, Clone)]
+pub struct TierClass {
+    pub id: ClassId,
+    pub desc: TierClassDescriptor,
+}
+
+/// One of the units that went into a merge
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergedPart {
+    pub class: ClassId,
+    pub size: UnitSize,
+    /// Units grow as they eat each other
+    pub scale: f32,
+    /// What the part was merged from itself
+    #[serde(default)]
+    pub merged_from: Option<MergedFrom>,
+}
+
+/// The units a unit was merged from, splitting it gives back exactly these
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergedFrom {
+    pub parts: Vec<MergedPart>,
+    /// Bloodrock, from the recipe of the merge
+    pub unmerge_cost: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeUnit {
+    pub class: ClassId,
+    /// Any size if missing
+    #[serde(default)]
+    pub size: Option<UnitSize>,
+}
+
+impl RecipeUnit {
+    fn matches(&self, class: &ClassId, size: UnitSize) -> bool {
+        self.class == *class && self.size.map_or(true, |s| s == size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeRecipe {
+    /// Either unit can be the one eating the other
+    pub inputs: [RecipeUnit; 2],
+    /// Keeps the size of the eating unit if the size is missing
+    pub output: RecipeUnit,
+    /// Bloodrock, the merge is rejected if the player can not pay
+    #[serde(default)]
+    pub cost: usize,
+    /// Bloodrock to split the output back into the inputs
+    #[serde(default = "default_unmerge_cost")]
+    pub unmerge_cost: usize,
+}
+
+fn default_unmerge_cost() -> usize {
+    2
+}
+
+/// Merge recipes as they are stored in `assets/units.recipes.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "3a6e1c8f-92b4-4d57-8e0a-5f1b7c2d9e43"]
+pub struct RecipesAsset {
+    #[serde(default)]
+    pub classes: HashMap<ClassId, TierClassDescriptor>,
+    pub recipes: Vec<MergeRecipe>,
+}
+
+pub fn parse_recipes(bytes: &[u8]) -> Result<RecipesAsset, anyhow::Error> {
+    let asset: RecipesAsset = serde_json::from_slice(bytes)
+        .map_err(|err| anyhow!("malformed recipe file: {}", err))?;
+    for (id, class) in asset.classes.iter() {
+        if UnitClass::from_name(&id.0).is_some() {
+            bail!("{} is already a basic class", id);
+        }
+        if class.tier < 2 {
+            bail!("{}: tier classes start at tier 2", id);
+        }
+        if class.damage <= 0. || class.health <= 0. {
+            bail!("{}: multipliers have to be positive", id);
+        }
+    }
+    let known = |id: &ClassId| {
+        UnitClass::from_name(&id.0).is_some() || asset.classes.contains_key(id)
+    };
+    for (index, recipe) in asset.recipes.iter().enumerate() {
+        for unit in recipe.inputs.iter().chain([&recipe.output]) {
+            if !known(&unit.class) {
+                bail!("recipe {}: unknown class {}", index, unit.class);
+            }
+        }
+    }
+    Ok(asset)
+}
+
+#[derive(Default)]
+pub struct RecipesLoader;
+
+impl AssetLoader for RecipesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let recipes = parse_recipes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(recipes));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes.json"]
+    }
+}
+
+/// Merge recipes of the loaded recipe file
+#[derive(Default)]
+pub struct MergeRecipes {
+    pub handle: Handle<RecipesAsset>,
+    pub classes: HashMap<ClassId, TierClassDescriptor>,
+    pub recipes: Vec<MergeRecipe>,
+}
+
+impl MergeRecipes {
+    pub fn is_loaded(&self) -> bool {
+        !self.recipes.is_empty()
+    }
+
+    /// The first recipe merging the two units, in either order
+    pub fn find(
+        &self,
+        eater: (&ClassId, UnitSize),
+        prey: (&ClassId, UnitSize),
+    ) -> Option<&MergeRecipe> {
+        self.recipes.iter().find(|recipe| {
+            let [a, b] = &recipe.inputs;
+            (a.matches(eater.0, eater.1) && b.matches(prey.0, prey.1))
+                || (a.matches(prey.0, prey.1) && b.matches(eater.0, eater.1))
+        })
+    }
+
+    /// The recipe to split a unit back up with, recipes for its exact size
+    /// win over the ones for any size
+    pub fn reverse(
+        &self,
+        class: &ClassId,
+        size: UnitSize,
+    ) -> Option<&MergeRecipe> {
+        let makes = |recipe: &&MergeRecipe| recipe.output.class == *class;
+        self.recipes
+            .iter()
+            .filter(makes)
+            .find(|recipe| recipe.output.size == Some(size))
+            .or_else(|| {
+                self.recipes
+                    .iter()
+                    .filter(makes)
+                    .find(|recipe| recipe.output.size.is_none())
+            })
+    }
+
+    /// The `UnitClass` of a class, with the tier class if it is one
+    pub fn resolve(
+        &self,
+        id: &ClassId,
+    ) -> Option<(UnitClass, Option<TierClass>)> {
+        if let Some(class) = UnitClass::from_name(&id.0) {
+            return Some((class, None));
+        }
+        self.classes.get(id).map(|desc| {
+            (
+                desc.base,
+                Some(TierClass {
+                    id: id.clone(),
+                    desc: desc.clone(),
+                }),
+            )
+        })
+    }
+}
+
+fn load_recipes_system(
+    asset_server: Res<AssetServer>,
+    mut recipes: ResMut<MergeRecipes>,
+) {
+    recipes.handle = asset_server.load(RECIPES_FILE);
+}
+
+/// Picks up the recipes once they finished loading, or got hot reloaded
+fn recipes_asset_event_system(
+    mut events: EventReader<AssetEvent<RecipesAsset>>,
+    assets: Res<Assets<RecipesAsset>>,
+    mut recipes: ResMut<MergeRecipes>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle }
+            | AssetEvent::Modified { handle } => {
+                if *handle != recipes.handle {
+                    continue;
+                }
+                let asset = match assets.get(handle) {
+                    Some(asset) => asset,
+                    None => continue,
+                };
+                info!(
+                    "loaded {} merge recipes and {} tier classes",
+                    asset.recipes.len(),
+                    asset.classes.len()
+                );
+                recipes.classes = asset.classes.clone();
+                recipes.recipes = asset.recipes.clone();
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+pub struct MergeRecipesPlugin;
+
+impl Plugin for MergeRecipesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<RecipesAsset>()
+            .init_asset_loader::<RecipesLoader>()
+            .init_resource::<MergeRecipes>()
+            .add_startup_system(load_recipes_system)
+            .add_system(recipes_asset_event_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipes(json: &str) -> MergeRecipes {
+        let asset = parse_recipes(json.as_bytes()).unwrap();
+        MergeRecipes {
+            classes: asset.classes,
+            recipes: asset.recipes,
+            ..Default::default()
+        }
+    }
+
+    const RECIPES: &str = r#"{
+        "classes": { "Paladin": { "base": "Sworder", "tier": 2 } },
+        "recipes": [
+            { "inputs": [{ "class": "Sworder", "size": "Small" }, { "class": "Healer" }],
+              "output": { "class": "Paladin" }, "unmerge_cost": 5 },
+            { "inputs": [{ "class": "Worker", "size": "Small" }, { "class": "Worker", "size": "Small" }],
+              "output": { "class": "Worker", "size": "Medium" } },
+            { "inputs": [{ "class": "Paladin" }, { "class": "Worker" }],
+              "output": { "class": "Worker" } }
+        ]
+    }"#;
+
+    #[test]
+    fn shipped_recipes_are_valid() {
+        parse_recipes(include_bytes!("../assets/units.recipes.json")).unwrap();
+    }
+
+    #[test]
+    fn find_matches_either_order_and_any_size() {
+        let recipes = recipes(RECIPES);
+        let sworder = ClassId::from("Sworder");
+        let healer = ClassId::from("Healer");
+        let paladin = ClassId::from("Paladin");
+
+        let eats = recipes
+            .find((&sworder, UnitSize::Small), (&healer, UnitSize::Huge))
+            .unwrap();
+        let eaten = recipes
+            .find((&healer, UnitSize::Medium), (&sworder, UnitSize::Small))
+            .unwrap();
+        assert_eq!(eats.output.class, paladin);
+        assert_eq!(eats, eaten);
+        assert!(recipes
+            .find((&sworder, UnitSize::Medium), (&healer, UnitSize::Small))
+            .is_none());
+    }
+
+    #[test]
+    fn reverse_prefers_the_exact_size() {
+        let recipes = recipes(RECIPES);
+        let worker = ClassId::from("Worker");
+
+        let exact = recipes.reverse(&worker, UnitSize::Medium).unwrap();
+        assert_eq!(exact.output.size, Some(UnitSize::Medium));
+        let any = recipes.reverse(&worker, UnitSize::Huge).unwrap();
+        assert_eq!(any.inputs[0].class, ClassId::from("Paladin"));
+        assert_eq!(
+            recipes
+                .reverse(&ClassId::from("Paladin"), UnitSize::Small)
+                .map(|r| r.unmerge_cost),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn resolve_knows_basic_and_tier_classes() {
+        let recipes = recipes(RECIPES);
+
+        let (class, tier) = recipes.resolve(&ClassId::from("Healer")).unwrap();
+        assert_eq!(class, UnitClass::Healer);
+        assert!(tier.is_none());
+        let (class, tier) = recipes.resolve(&ClassId::from("Paladin")).unwrap();
+        assert_eq!(class, UnitClass::Sworder);
+        assert_eq!(tier.unwrap().desc.tier, 2);
+        assert!(recipes.resolve(&ClassId::from("Wizard")).is_none());
+    }
+
+    #[test]
+    fn unknown_classes_are_rejected() {
+        let err = parse_recipes(
+            br#"{ "recipes": [
+                { "inputs": [{ "class": "Worker" }, { "class": "Worker" }],
+                  "output": { "class": "Worker" } },
+                { "inputs": [{ "class": "Worker" }, { "class": "Wizard" }],
+                  "output": { "class": "Worker" } }
+            ] }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "recipe 1: unknown class Wizard");
+
+        let err = parse_recipes(
+            br#"{ "classes": { "Tank": { "base": "Tank", "tier": 2 } },
+                  "recipes": [] }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Tank is already a basic class");
+    }
+}
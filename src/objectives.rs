@@ -0,0 +1,345 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy_logic::{BasicEnemyLogic, EnemyId, LevelManager},
+    game::{BloodrockAmount, ResourceAssets},
+    health::{Health, UnitDiedEvent},
+    ui::{EndGameManager, EndGameState},
+    GameMode, GameTime, SceneState,
+};
+
+/// Something the player has to do, or must not let happen, to win a level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ObjectiveGoal {
+    /// Every wave spawned and no enemies left
+    KillAllWaves,
+    Survive {
+        seconds: f32,
+    },
+    /// Counts every bloodrock earned during the level, spent or not
+    GatherBloodrock {
+        amount: usize,
+    },
+    /// Enemies attack a structure at `position`, the objective fails if it
+    /// gets destroyed
+    Protect {
+        position: [f32; 2],
+        #[serde(default = "default_structure_health")]
+        health: f32,
+    },
+    KillBoss {
+        kind: EnemyId,
+    },
+    /// Fails once more than `max_lost` units died
+    LimitUnitLosses {
+        max_lost: usize,
+    },
+}
+
+fn default_structure_health() -> f32 {
+    30.
+}
+
+impl ObjectiveGoal {
+    /// Constraints can only fail, they count as completed when the level is
+    /// won
+    pub fn is_constraint(&self) -> bool {
+        matches!(
+            self,
+            ObjectiveGoal::Protect { .. }
+                | ObjectiveGoal::LimitUnitLosses { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectiveDescriptor {
+    #[serde(flatten)]
+    pub goal: ObjectiveGoal,
+    /// Secondary objectives do not decide if the level is won or lost
+    #[serde(default)]
+    pub secondary: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectiveStatus {
+    InProgress,
+    Completed,
+    Failed,
+}
+
+pub struct Objective {
+    pub goal: ObjectiveGoal,
+    pub secondary: bool,
+    pub status: ObjectiveStatus,
+    /// Waves spawned, seconds survived, bloodrock gathered, structure health,
+    /// bosses killed or units lost
+    pub progress: f32,
+    pub target: f32,
+    structure: Option<Entity>,
+}
+
+impl Objective {
+    pub fn describe(&self) -> String {
+        let label = match &self.goal {
+            ObjectiveGoal::KillAllWaves => "Defeat every wave".to_string(),
+            ObjectiveGoal::Survive { .. } => "Survive".to_string(),
+            ObjectiveGoal::GatherBloodrock { .. } => {
+                "Gather bloodrock".to_string()
+            }
+            ObjectiveGoal::Protect { .. } => "Protect the nest".to_string(),
+            ObjectiveGoal::KillBoss { kind } => format!("Kill the {}", kind),
+            ObjectiveGoal::LimitUnitLosses { .. } => {
+                "Units lost, at most".to_string()
+            }
+        };
+        let status = match self.status {
+            ObjectiveStatus::InProgress => "[ ]",
+            ObjectiveStatus::Completed => "[x]",
+            ObjectiveStatus::Failed => "[-]",
+        };
+        let optional = if self.secondary { " (optional)" } else { "" };
+        format!(
+            "{} {}{}: {:.0} / {:.0}",
+            status, label, optional, self.progress, self.target
+        )
+    }
+}
+
+/// Objectives of the level in progress, empty in endless mode
+#[derive(Default)]
+pub struct Objectives {
+    pub list: Vec<Objective>,
+    /// Bloodrock amount on the last update, to see how much was gathered
+    last_bloodrock: Option<usize>,
+}
+
+/// Progress of one objective in a save
+#[derive(Serialize, Deserialize)]
+pub struct SavedObjective {
+    pub status: ObjectiveStatus,
+    pub progress: f32,
+}
+
+impl Objectives {
+    pub fn save(&self) -> Vec<SavedObjective> {
+        self.list
+            .iter()
+            .map(|objective| SavedObjective {
+                status: objective.status,
+                progress: objective.progress,
+            })
+            .collect()
+    }
+
+    /// The protected structures come back at full health
+    pub fn restore(&mut self, saved: &[SavedObjective]) {
+        if saved.len() != self.list.len() {
+            warn!("Saved objectives do not match the level, ignoring them");
+            return;
+        }
+        for (objective, saved) in self.list.iter_mut().zip(saved) {
+            if objective.structure.is_none() {
+                objective.progress = saved.progress;
+            }
+            objective.status = saved.status;
+        }
+        self.last_bloodrock = None;
+    }
+}
+
+#[derive(Component)]
+pub struct ProtectedStructure;
+
+fn reset_objectives_system(mut objectives: ResMut<Objectives>) {
+    *objectives = Objectives::default();
+}
+
+fn start_objectives_system(
+    mut cmd: Commands,
+    mut objectives: ResMut<Objectives>,
+    level_manager: Res<LevelManager>,
+    game_mode: Res<GameMode>,
+    resource_assets: Res<ResourceAssets>,
+) {
+    *objectives = Objectives::default();
+    if *game_mode != GameMode::Campaign {
+        return;
+    }
+    let level = &level_manager.current_level;
+    for desc in level.objectives.iter() {
+        let mut structure = None;
+        let target = match desc.goal {
+            ObjectiveGoal::KillAllWaves => level.waves.len() as f32,
+            ObjectiveGoal::Survive { seconds } => seconds,
+            ObjectiveGoal::GatherBloodrock { amount } => amount as f32,
+            ObjectiveGoal::Protect { position, health } => {
+                structure = Some(spawn_structure(
+                    &mut cmd,
+                    &resource_assets,
+                    Vec2::from(position).extend(0.),
+                    health,
+                ));
+                health
+            }
+            ObjectiveGoal::KillBoss { .. } => 1.,
+            ObjectiveGoal::LimitUnitLosses { max_lost } => max_lost as f32,
+        };
+        objectives.list.push(Objective {
+            goal: desc.goal.clone(),
+            secondary: desc.secondary,
+            status: ObjectiveStatus::InProgress,
+            progress: 0.,
+            target,
+            structure,
+        });
+    }
+}
+
+fn spawn_structure(
+    cmd: &mut Commands,
+    resource_assets: &ResourceAssets,
+    pos: Vec3,
+    health: f32,
+) -> Entity {
+    let mut tr = Transform::from_translation(pos);
+    tr.scale = Vec3::splat(2.);
+    cmd.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: resource_assets.bloodrock_node.clone(),
+        sprite: TextureAtlasSprite {
+            color: Color::rgb(0.6, 1., 0.6),
+            ..Default::default()
+        },
+        transform: tr,
+        ..Default::default()
+    })
+    .insert(Health {
+        max_health: health,
+        current_health: health,
+        armor: 0.,
+    })
+    .insert(ProtectedStructure)
+    .id()
+}
+
+fn objectives_system(
+    game_time: Res<GameTime>,
+    mut objectives: ResMut<Objectives>,
+    level_manager: Res<LevelManager>,
+    bloodrock: Res<BloodrockAmount>,
+    mut died_events: EventReader<UnitDiedEvent>,
+    enemies: Query<Entity, With<BasicEnemyLogic>>,
+    structures: Query<&Health, With<ProtectedStructure>>,
+    mut end_game: ResMut<EndGameManager>,
+) {
+    if objectives.list.is_empty() {
+        return;
+    }
+    let objectives = &mut *objectives;
+    if matches!(end_game.state, EndGameState::NotEndGame) {
+        let gathered = objectives
+            .last_bloodrock
+            .map_or(0, |last| bloodrock.0.saturating_sub(last));
+        objectives.last_bloodrock = Some(bloodrock.0);
+        let mut allies_died = 0;
+        let mut killed = Vec::new();
+        for event in died_events.iter() {
+            if event.ally {
+                allies_died += 1;
+            }
+            killed.extend(event.enemy_kind.clone());
+        }
+        let level = &level_manager.current_level;
+        let waves_done = level.current_wave_index >= level.waves.len();
+
+        for objective in objectives.list.iter_mut() {
+            if objective.status != ObjectiveStatus::InProgress {
+                continue;
+            }
+            match &objective.goal {
+                ObjectiveGoal::KillAllWaves => {
+                    objective.progress = level.current_wave_index as f32;
+                    if waves_done && enemies.iter().len() < 1 {
+                        objective.status = ObjectiveStatus::Completed;
+                    }
+                }
+                ObjectiveGoal::Survive { .. } => {
+                    objective.progress += game_time.delta_seconds();
+                    if objective.progress >= objective.target {
+                        objective.status = ObjectiveStatus::Completed;
+                    }
+                }
+                ObjectiveGoal::GatherBloodrock { .. } => {
+                    objective.progress += gathered as f32;
+                    if objective.progress >= objective.target {
+                        objective.status = ObjectiveStatus::Completed;
+                    }
+                }
+                ObjectiveGoal::Protect { .. } => {
+                    objective.progress = objective
+                        .structure
+                        .and_then(|structure| structures.get(structure).ok())
+                        .map_or(0., |health| health.current_health);
+                    if objective.progress <= 0. {
+                        objective.status = ObjectiveStatus::Failed;
+                    }
+                }
+                ObjectiveGoal::KillBoss { kind } => {
+                    if killed.contains(kind) {
+                        objective.progress = 1.;
+                        objective.status = ObjectiveStatus::Completed;
+                    }
+                }
+                ObjectiveGoal::LimitUnitLosses { .. } => {
+                    objective.progress += allies_died as f32;
+                    if objective.progress > objective.target {
+                        objective.status = ObjectiveStatus::Failed;
+                    }
+                }
+            }
+        }
+
+        let primary = || objectives.list.iter().filter(|o| !o.secondary);
+        if primary().any(|o| o.status == ObjectiveStatus::Failed) {
+            end_game.state = EndGameState::Lose;
+        } else if primary().all(|o| {
+            o.goal.is_constraint() || o.status == ObjectiveStatus::Completed
+        }) {
+            end_game.state = EndGameState::Win;
+            for objective in objectives.list.iter_mut() {
+                if objective.status == ObjectiveStatus::InProgress {
+                    objective.status = if objective.goal.is_constraint() {
+                        ObjectiveStatus::Completed
+                    } else {
+                        ObjectiveStatus::Failed
+                    };
+                }
+            }
+        }
+    }
+
+    end_game.objectives =
+        objectives.list.iter().map(|o| o.describe()).collect();
+}
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Objectives>()
+            .add_system_set(
+                SystemSet::on_enter(SceneState::InGame)
+                    .with_system(start_objectives_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::InGame)
+                    .with_system(objectives_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::MainMenu)
+                    .with_system(reset_objectives_system),
+            );
+    }
+}
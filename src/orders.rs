@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+use crate::{
+    combat::{AttackState, CombatComponent},
+    enemy_logic::BasicEnemyLogic,
+    game::{BloodrockNode, Harvester, Velocity},
+    interaction::{
+        box_select_system, BoxSelected, CursorPosition, MouseFollow,
+    },
+    replay::PlayerInput,
+    worker_logic::{HealerComponent, HealingState},
+    GameTime, SceneState,
+};
+
+/// How close to an enemy or a node a right click has to be to target it
+const ORDER_PICK_RANGE: f32 = 80.;
+/// Units ordered to the same spot spread out this far from each other
+const MOVE_SPREAD: f32 = 45.;
+const ARRIVE_DISTANCE: f32 = 10.;
+
+/// Order given by right clicking, the unit ignores its own targeting until
+/// the order is done
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum UnitOrder {
+    MoveTo(Vec2),
+    /// Done once the enemy is dead
+    Attack(Entity),
+    /// Done once the node is depleted
+    Harvest(Entity),
+}
+
+/// The closest entity in pick range of `pos`
+fn pick<'a>(
+    pos: Vec2,
+    candidates: impl Iterator<Item = (Entity, &'a GlobalTransform)>,
+) -> Option<Entity> {
+    candidates
+        .map(|(entity, tr)| {
+            (entity, (tr.translation().truncate() - pos).length())
+        })
+        .filter(|(_, distance)| *distance <= ORDER_PICK_RANGE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+/// Spreads units moving to the same spot on a spiral around it
+fn spread_offset(index: usize) -> Vec2 {
+    // golden angle, in radians
+    let angle = index as f32 * 2.4;
+    Vec2::new(angle.cos(), angle.sin()) * MOVE_SPREAD * (index as f32).sqrt()
+}
+
+fn issue_orders_system(
+    mut cmd: Commands,
+    input: Res<PlayerInput>,
+    cursor: Res<CursorPosition>,
+    selection: Query<
+        (Entity, Option<&CombatComponent>, Option<&Harvester>),
+        With<BoxSelected>,
+    >,
+    enemies: Query<(Entity, &GlobalTransform), With<BasicEnemyLogic>>,
+    nodes: Query<(Entity, &GlobalTransform), With<BloodrockNode>>,
+) {
+    if !input.order {
+        return;
+    }
+    let pos = match cursor.0 {
+        Some(pos) => pos,
+        None => return,
+    };
+    let enemy = pick(pos, enemies.iter());
+    let node = pick(pos, nodes.iter());
+    let mut moving = 0;
+    for (entity, combat, harvester) in selection.iter() {
+        // units that can not do what was clicked on just walk there
+        let order = match (enemy, node) {
+            (Some(enemy), _) if combat.is_some() => UnitOrder::Attack(enemy),
+            (_, Some(node)) if harvester.is_some() => UnitOrder::Harvest(node),
+            _ => {
+                moving += 1;
+                UnitOrder::MoveTo(pos + spread_offset(moving - 1))
+            }
+        };
+        debug!("{:?} got ordered to {:?}", entity, order);
+        cmd.entity(entity).insert(order);
+    }
+}
+
+fn follow_orders_system(
+    mut cmd: Commands,
+    time: Res<GameTime>,
+    mut units: Query<
+        (
+            Entity,
+            &UnitOrder,
+            &mut Transform,
+            &Velocity,
+            Option<&mut CombatComponent>,
+            Option<&mut Harvester>,
+            Option<&mut HealerComponent>,
+        ),
+        Without<MouseFollow>,
+    >,
+    targets: Query<&GlobalTransform>,
+) {
+    for (entity, order, mut tr, vel, combat, harvester, healer) in
+        units.iter_mut()
+    {
+        let done = match *order {
+            UnitOrder::MoveTo(destination) => {
+                // swings that already started still land
+                if let Some(mut combat) = combat {
+                    if matches!(combat.attack_state, AttackState::NotAttacking)
+                    {
+                        combat.target = None;
+                    }
+                }
+                if let Some(mut healer) = healer {
+                    if matches!(healer.state, HealingState::Idle) {
+                        healer.target = None;
+                    }
+                }
+                let to_destination = destination - tr.translation.truncate();
+                let step = time.delta_seconds() * vel.0;
+                if to_destination.length() <= step.max(ARRIVE_DISTANCE) {
+                    true
+                } else {
+                    tr.translation +=
+                        (to_destination.normalize() * step).extend(0.);
+                    false
+                }
+            }
+            UnitOrder::Attack(enemy) => match (targets.get(enemy), combat) {
+                (Ok(_), Some(mut combat)) => {
+                    if combat.target != Some(enemy)
+                        && matches!(
+                            combat.attack_state,
+                            AttackState::NotAttacking
+                        )
+                    {
+                        combat.target = Some(enemy);
+                    }
+                    false
+                }
+                _ => true,
+            },
+            UnitOrder::Harvest(node) => match (targets.get(node), harvester) {
+                (Ok(_), Some(mut harvester)) => {
+                    // full harvesters bring their load back first
+                    if harvester.current_carried_resource
+                        < harvester.max_carryable_resource
+                    {
+                        harvester.target_node = Some(node);
+                    }
+                    false
+                }
+                _ => true,
+            },
+        };
+        if done {
+            cmd.entity(entity).remove::<UnitOrder>();
+        }
+    }
+}
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(issue_orders_system.after(box_select_system))
+                .with_system(follow_orders_system),
+        );
+    }
+}
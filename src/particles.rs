@@ -3,7 +3,7 @@ use std::{f32::consts::TAU, time::Duration};
 use bevy::prelude::*;
 use rand::Rng;
 
-use crate::{easing::Easing, lerp::Lerp, GameTime};
+use crate::{easing::Easing, lerp::Lerp, rng::GameRng, GameTime};
 
 #[derive(Default, Clone, Copy, Component)]
 pub struct Velocity(pub Vec3);
@@ -86,8 +86,9 @@ fn update_emitters_system(
         (&mut SpawnTimer, &SpawnConfig, &GlobalTransform),
         Without<Disabled>,
     >,
+    mut rng: ResMut<GameRng>,
 ) {
-    let mut rng = rand::thread_rng();
+    let rng = &mut rng.cosmetic;
     let delta = time.delta();
     q.for_each_mut(move |(mut t, conf, tr)| {
         t.0.tick(delta);
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy_logic::Arena,
+    game::{BloodrockNode, Harvester, Velocity},
+    health::DestroyEntity,
+    GameTime, SceneState,
+};
+
+/// How close a raider has to get to steal
+const STEAL_RANGE: f32 = 60.;
+/// How far past the arena edge an escaping raider has to get
+const ESCAPE_MARGIN: f32 = 200.;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaiderDescriptor {
+    /// Bloodrock it can carry before escaping
+    pub capacity: usize,
+    /// Bloodrock taken from a node per steal
+    #[serde(default = "default_steal_amount")]
+    pub steal_amount: usize,
+    /// Seconds between steals
+    #[serde(default = "default_steal_time")]
+    pub steal_time: f32,
+}
+
+fn default_steal_amount() -> usize {
+    1
+}
+
+fn default_steal_time() -> f32 {
+    1.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RaiderState {
+    Hunting {
+        target: Option<Entity>,
+    },
+    /// Runs off the arena with the loot
+    Escaping {
+        direction: Vec2,
+    },
+}
+
+#[derive(Component)]
+pub struct Raider {
+    pub state: RaiderState,
+    /// Stolen bloodrock, the player gets it back by killing the raider
+    pub carried: usize,
+    capacity: usize,
+    steal_amount: usize,
+    steal_timer: Timer,
+}
+
+impl Raider {
+    pub fn new(desc: &RaiderDescriptor) -> Self {
+        Raider {
+            state: RaiderState::Hunting { target: None },
+            carried: 0,
+            capacity: desc.capacity,
+            steal_amount: desc.steal_amount,
+            steal_timer: Timer::from_seconds(desc.steal_time, true),
+        }
+    }
+
+    /// Picks up a saved raider, hunting raiders look for a new target
+    pub fn restored(desc: &RaiderDescriptor, saved: &SavedRaider) -> Self {
+        let mut raider = Raider::new(desc);
+        raider.carried = saved.carried;
+        if let Some(direction) = saved.escape_direction {
+            raider.state = RaiderState::Escaping {
+                direction: Vec2::from(direction),
+            };
+        }
+        raider
+    }
+
+    pub fn save(&self) -> SavedRaider {
+        SavedRaider {
+            carried: self.carried,
+            escape_direction: match self.state {
+                RaiderState::Escaping { direction } => Some(direction.into()),
+                RaiderState::Hunting { .. } => None,
+            },
+        }
+    }
+
+    pub fn is_escaping(&self) -> bool {
+        matches!(self.state, RaiderState::Escaping { .. })
+    }
+}
+
+/// What a raider has stolen so far and where it is running off to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedRaider {
+    pub carried: usize,
+    /// Only set once it is escaping
+    pub escape_direction: Option<[f32; 2]>,
+}
+
+/// Straight out through the closest arena edge
+fn escape_direction(arena: &Arena, pos: Vec2) -> Vec2 {
+    [
+        (pos.x - arena.min.x, -Vec2::X),
+        (arena.max.x - pos.x, Vec2::X),
+        (pos.y - arena.min.y, -Vec2::Y),
+        (arena.max.y - pos.y, Vec2::Y),
+    ]
+    .into_iter()
+    .min_by(|(a, _), (b, _)| a.total_cmp(b))
+    .map_or(Vec2::X, |(_, direction)| direction)
+}
+
+fn raider_system(
+    time: Res<GameTime>,
+    arena: Res<Arena>,
+    mut raiders: Query<(
+        Entity,
+        &mut Raider,
+        &mut Transform,
+        &GlobalTransform,
+        &Velocity,
+    )>,
+    mut harvesters: Query<(Entity, &mut Harvester, &GlobalTransform)>,
+    mut nodes: Query<(Entity, &mut BloodrockNode, &GlobalTransform)>,
+    mut destroy_event_writer: EventWriter<DestroyEntity>,
+) {
+    for (entity, mut raider, mut tr, global_tr, vel) in raiders.iter_mut() {
+        let pos = global_tr.translation().truncate();
+
+        if let RaiderState::Escaping { direction } = raider.state {
+            tr.translation +=
+                direction.extend(0.) * vel.0 * time.delta_seconds();
+            if pos.x < arena.min.x - ESCAPE_MARGIN
+                || pos.x > arena.max.x + ESCAPE_MARGIN
+                || pos.y < arena.min.y - ESCAPE_MARGIN
+                || pos.y > arena.max.y + ESCAPE_MARGIN
+            {
+                // got away, the loot is gone
+                destroy_event_writer.send(DestroyEntity(entity));
+            }
+            continue;
+        }
+
+        let target = match raider.state {
+            RaiderState::Hunting { target } => target,
+            RaiderState::Escaping { .. } => None,
+        };
+        let target_pos = target.and_then(|target| {
+            harvesters
+                .get(target)
+                .ok()
+                .filter(|(_, harvester, _)| {
+                    harvester.current_carried_resource > 0
+                })
+                .map(|(_, _, tr)| tr.translation().truncate())
+                .or_else(|| {
+                    nodes
+                        .get(target)
+                        .ok()
+                        .filter(|(_, node, _)| node.amount_of_resource > 0)
+                        .map(|(_, _, tr)| tr.translation().truncate())
+                })
+        });
+
+        let (target, target_pos) = match target.zip(target_pos) {
+            Some(found) => found,
+            None => {
+                let loaded = harvesters
+                    .iter()
+                    .filter(|(_, harvester, _)| {
+                        harvester.current_carried_resource > 0
+                    })
+                    .map(|(e, _, tr)| (e, tr.translation().truncate()));
+                let filled = nodes
+                    .iter()
+                    .filter(|(_, node, _)| node.amount_of_resource > 0)
+                    .map(|(e, _, tr)| (e, tr.translation().truncate()));
+                let closest = loaded.chain(filled).min_by(|(_, a), (_, b)| {
+                    (*a - pos)
+                        .length_squared()
+                        .total_cmp(&(*b - pos).length_squared())
+                });
+                match closest {
+                    Some(closest) => closest,
+                    // nothing left to steal, leave with what it has
+                    None => {
+                        raider.state = if raider.carried > 0 {
+                            RaiderState::Escaping {
+                                direction: escape_direction(&arena, pos),
+                            }
+                        } else {
+                            RaiderState::Hunting { target: None }
+                        };
+                        continue;
+                    }
+                }
+            }
+        };
+        raider.state = RaiderState::Hunting {
+            target: Some(target),
+        };
+
+        let offset = target_pos - pos;
+        if offset.length() > STEAL_RANGE {
+            tr.translation += offset.normalize_or_zero().extend(0.)
+                * vel.0
+                * time.delta_seconds();
+            continue;
+        }
+
+        raider.steal_timer.tick(time.delta());
+        if !raider.steal_timer.just_finished() {
+            continue;
+        }
+        let room = raider.capacity.saturating_sub(raider.carried);
+        if let Ok((_, mut harvester, _)) = harvesters.get_mut(target) {
+            let stolen = harvester.current_carried_resource.min(room);
+            harvester.current_carried_resource -= stolen;
+            raider.carried += stolen;
+        } else if let Ok((node_entity, mut node, _)) = nodes.get_mut(target) {
+            let stolen =
+                node.amount_of_resource.min(raider.steal_amount).min(room);
+            node.amount_of_resource -= stolen;
+            raider.carried += stolen;
+            if node.amount_of_resource == 0 {
+                destroy_event_writer.send(DestroyEntity(node_entity));
+            }
+        }
+
+        if raider.carried >= raider.capacity {
+            raider.state = RaiderState::Escaping {
+                direction: escape_direction(&arena, pos),
+            };
+        }
+    }
+}
+
+pub struct RaiderPlugin;
+
+impl Plugin for RaiderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame).with_system(raider_system),
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration};
+
+use crate::{
+    difficulty::{Difficulty, DifficultySettings},
+    enemy_logic::LevelManager,
+    game::{handle_keyboard_movement, BloodrockAmount},
+    headless::LevelOutcome,
+    interaction::window_to_world,
+    rng::GameRng,
+    storage,
+    ui::{EndGameManager, EndGameState},
+    GameMode, GameTime, PlayerCamera, SceneState, GAME_TIME_UPDATE,
+    UNLOAD_LEVEL,
+};
+
+/// Bump this whenever the replay format or the meaning of an input changes
+pub const REPLAY_VERSION: u32 = 5;
+
+const LAST_REPLAY_FILE: &str = "last_replay.json";
+
+/// Player input of the current tick
+///
+/// Gameplay systems read this instead of the keyboard and mouse, so a replay
+/// can feed them the exact same input.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerInput {
+    /// Keyboard movement direction, not normalized
+    pub movement: [f32; 2],
+    /// Space: spawn a unit
+    pub spawn_unit: bool,
+    /// F: damage the enemies around the player
+    pub damage_pulse: bool,
+    /// N: spawn the next wave now, for bonus bloodrock
+    pub call_wave: bool,
+    /// World position of the cursor, if it moved this tick
+    pub cursor: Option<[f32; 2]>,
+    /// Left mouse button got pressed
+    pub select: bool,
+    /// Left mouse button got released
+    pub release: bool,
+    /// U: split the hovered unit back into the units it was merged from
+    pub unmerge: bool,
+    /// Shift held while selecting, adds to the box selection instead of
+    /// replacing it
+    pub add_to_selection: bool,
+    /// Right mouse button got pressed: order the box selected units
+    pub order: bool,
+    /// G: switch to the next formation
+    pub switch_formation: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickInput {
+    pub tick: u64,
+    #[serde(flatten)]
+    pub input: PlayerInput,
+}
+
+/// State of the game at the end of a recording, playback has to match it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplaySummary {
+    pub bloodrock: usize,
+    pub wave_index: usize,
+    /// `None` if the player left before the level was over
+    pub outcome: Option<LevelOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub game_mode: GameMode,
+    /// Asset path of the level, empty in endless mode
+    pub level: String,
+    pub difficulty: DifficultySettings,
+    /// Length of every tick in microseconds, the simulation speed follows the
+    /// frame rate so it has to be replayed as well
+    pub frame_deltas: Vec<u32>,
+    /// Only the ticks that had any input
+    pub inputs: Vec<TickInput>,
+    pub summary: Option<ReplaySummary>,
+}
+
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+impl Replay {
+    fn new(
+        seed: u64,
+        game_mode: GameMode,
+        level: String,
+        difficulty: DifficultySettings,
+    ) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            game_mode,
+            level,
+            difficulty,
+            frame_deltas: Vec::new(),
+            inputs: Vec::new(),
+            summary: None,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        // check the version first, so old replays do not fail with a
+        // confusing missing field error
+        let header: ReplayHeader = serde_json::from_slice(&bytes)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        if header.version != REPLAY_VERSION {
+            return Err(format!(
+                "{}: replay version {} is not supported, expected {}",
+                path.display(),
+                header.version,
+                REPLAY_VERSION
+            ));
+        }
+        serde_json::from_slice(&bytes)
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+/// Records every run, or plays one back
+pub struct ReplayState {
+    pub replay: Replay,
+    playback: bool,
+    /// Playback was requested, but the level has not been started yet
+    pending_playback: bool,
+    /// Ticks simulated in the current run
+    tick: u64,
+    next_input: usize,
+    /// Nothing gets recorded or played back until the next run
+    finished: bool,
+    /// Set once playback reached the end of the replay
+    pub playback_result: Option<Result<ReplaySummary, String>>,
+}
+
+impl ReplayState {
+    pub fn record() -> Self {
+        ReplayState {
+            replay: Replay::new(
+                0,
+                GameMode::Campaign,
+                String::new(),
+                DifficultySettings::default(),
+            ),
+            playback: false,
+            pending_playback: false,
+            tick: 0,
+            next_input: 0,
+            finished: true,
+            playback_result: None,
+        }
+    }
+
+    /// Starts `replay` from the main menu
+    pub fn play(replay: Replay) -> Self {
+        ReplayState {
+            replay,
+            playback: true,
+            pending_playback: true,
+            ..ReplayState::record()
+        }
+    }
+
+    /// Stops recording and saves the replay
+    fn finish_recording(&mut self, summary: ReplaySummary) {
+        self.finished = true;
+        self.replay.summary = Some(summary);
+        match storage::save(LAST_REPLAY_FILE, &self.replay) {
+            Ok(()) => info!("Saved replay of {} ticks", self.tick),
+            Err(err) => warn!("Failed to save replay: {}", err),
+        }
+    }
+
+    fn finish_playback(&mut self, summary: ReplaySummary) {
+        self.finished = true;
+        // later runs get recorded again
+        self.playback = false;
+        let result = match &self.replay.summary {
+            Some(expected) if *expected != summary => Err(format!(
+                "replay desynced, expected {:?}, got {:?}",
+                expected, summary
+            )),
+            _ => Ok(summary),
+        };
+        match &result {
+            Ok(_) => info!("Replay finished after {} ticks", self.tick),
+            Err(err) => warn!("{}", err),
+        }
+        self.playback_result = Some(result);
+    }
+
+    /// Stops recording the current run without saving it
+    pub fn discard_run(&mut self) {
+        if !self.playback {
+            self.finished = true;
+        }
+    }
+
+    fn next_recorded_input(&mut self) -> PlayerInput {
+        match self.replay.inputs.get(self.next_input) {
+            Some(recorded) if recorded.tick == self.tick => {
+                self.next_input += 1;
+                recorded.input.clone()
+            }
+            _ => PlayerInput::default(),
+        }
+    }
+}
+
+impl Default for ReplayState {
+    fn default() -> Self {
+        ReplayState::record()
+    }
+}
+
+fn summarize(
+    bloodrock: &BloodrockAmount,
+    level_manager: &LevelManager,
+    end_game_manager: &EndGameManager,
+) -> ReplaySummary {
+    ReplaySummary {
+        bloodrock: bloodrock.0,
+        wave_index: level_manager.current_level.current_wave_index,
+        outcome: match end_game_manager.state {
+            EndGameState::Win => Some(LevelOutcome::Win),
+            EndGameState::Lose => Some(LevelOutcome::Lose),
+            EndGameState::NotEndGame => None,
+        },
+    }
+}
+
+fn read_device_input(
+    keys: &Input<KeyCode>,
+    buttons: &Input<MouseButton>,
+    cursor_moved: &mut EventReader<CursorMoved>,
+    windows: Option<&Windows>,
+    cameras: &Query<
+        (&GlobalTransform, &OrthographicProjection),
+        With<PlayerCamera>,
+    >,
+) -> PlayerInput {
+    let mut movement = Vec2::ZERO;
+    let mut spawn_unit = false;
+    let mut damage_pulse = false;
+    handle_keyboard_movement(
+        &mut movement,
+        &mut spawn_unit,
+        &mut damage_pulse,
+        keys,
+    );
+
+    let mut cursor = None;
+    for m in cursor_moved.iter() {
+        let win = match windows.and_then(|windows| windows.get(m.id)) {
+            Some(win) => win,
+            None => continue,
+        };
+        for (cam_tr, proj) in cameras.iter() {
+            let cursor_world = window_to_world(m.position, win, *cam_tr, proj);
+            cursor = Some([cursor_world.x, cursor_world.y]);
+        }
+    }
+
+    let select = buttons.just_pressed(MouseButton::Left);
+    let release = buttons.just_released(MouseButton::Left);
+    // only recorded when it matters, holding shift would record every tick
+    let add_to_selection = (select || release)
+        && (keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift));
+
+    PlayerInput {
+        movement: movement.into(),
+        spawn_unit,
+        damage_pulse,
+        call_wave: keys.just_pressed(KeyCode::N),
+        cursor,
+        select,
+        release,
+        unmerge: keys.just_pressed(KeyCode::U),
+        add_to_selection,
+        order: buttons.just_pressed(MouseButton::Right),
+        switch_formation: keys.just_pressed(KeyCode::G),
+    }
+}
+
+fn replay_tick_system(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    windows: Option<Res<Windows>>,
+    cameras: Query<
+        (&GlobalTransform, &OrthographicProjection),
+        With<PlayerCamera>,
+    >,
+    bloodrock: Res<BloodrockAmount>,
+    level_manager: Res<LevelManager>,
+    end_game_manager: Res<EndGameManager>,
+    mut game_time: ResMut<GameTime>,
+    mut input: ResMut<PlayerInput>,
+    mut state: ResMut<ReplayState>,
+) {
+    let live = read_device_input(
+        &keys,
+        &buttons,
+        &mut cursor_moved,
+        windows.as_deref(),
+        &cameras,
+    );
+    if state.finished {
+        *input = live;
+        return;
+    }
+
+    if state.playback {
+        let tick = state.tick as usize;
+        match state.replay.frame_deltas.get(tick).copied() {
+            Some(micros) => {
+                game_time.real_delta = Duration::from_micros(micros as u64);
+                *input = state.next_recorded_input();
+                state.tick += 1;
+            }
+            None => {
+                state.finish_playback(summarize(
+                    &bloodrock,
+                    &level_manager,
+                    &end_game_manager,
+                ));
+                *input = live;
+            }
+        }
+        return;
+    }
+
+    if !matches!(end_game_manager.state, EndGameState::NotEndGame) {
+        state.finish_recording(summarize(
+            &bloodrock,
+            &level_manager,
+            &end_game_manager,
+        ));
+        *input = live;
+        return;
+    }
+    let tick = state.tick;
+    // step with the stored precision, so playback runs the exact same deltas
+    let micros = game_time.real_delta.as_micros() as u32;
+    game_time.real_delta = Duration::from_micros(micros as u64);
+    state.replay.frame_deltas.push(micros);
+    if live != PlayerInput::default() {
+        state.replay.inputs.push(TickInput {
+            tick,
+            input: live.clone(),
+        });
+    }
+    state.tick += 1;
+    *input = live;
+}
+
+fn start_run_system(
+    asset_server: Res<AssetServer>,
+    rng: Res<GameRng>,
+    game_mode: Res<GameMode>,
+    level_manager: Res<LevelManager>,
+    mut difficulty: ResMut<Difficulty>,
+    mut state: ResMut<ReplayState>,
+) {
+    state.tick = 0;
+    state.next_input = 0;
+    state.finished = false;
+    if state.playback {
+        return;
+    }
+    difficulty.playback = None;
+    let level = match *game_mode {
+        GameMode::Campaign => asset_server
+            .get_handle_path(&level_manager.level_handle)
+            .map(|path| path.path().to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        GameMode::Endless => String::new(),
+    };
+    state.replay =
+        Replay::new(rng.seed(), *game_mode, level, difficulty.settings());
+}
+
+/// Saves the recording when the player leaves in the middle of a level
+fn leave_run_system(
+    bloodrock: Res<BloodrockAmount>,
+    level_manager: Res<LevelManager>,
+    end_game_manager: Res<EndGameManager>,
+    mut state: ResMut<ReplayState>,
+) {
+    if state.playback || state.finished || state.tick == 0 {
+        return;
+    }
+    state.finish_recording(summarize(
+        &bloodrock,
+        &level_manager,
+        &end_game_manager,
+    ));
+}
+
+fn start_playback_system(
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<ReplayState>,
+    mut rng: ResMut<GameRng>,
+    mut game_mode: ResMut<GameMode>,
+    mut level_manager: ResMut<LevelManager>,
+    mut difficulty: ResMut<Difficulty>,
+    mut app_state: ResMut<State<SceneState>>,
+) {
+    if !state.pending_playback {
+        return;
+    }
+    state.pending_playback = false;
+    let replay = &state.replay;
+    info!(
+        "Playing back replay of {} ticks with seed {}",
+        replay.frame_deltas.len(),
+        replay.seed
+    );
+    *rng = GameRng::from_seed(replay.seed);
+    *game_mode = replay.game_mode;
+    difficulty.playback = Some(replay.difficulty);
+    if !replay.level.is_empty() {
+        level_manager.level_handle = asset_server.load(replay.level.as_str());
+    }
+    app_state.set(SceneState::LoadingLevel).unwrap_or_default();
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayState>()
+            .init_resource::<PlayerInput>()
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(SceneState::InGame).with_system(
+                    replay_tick_system
+                        .after(InputSystem)
+                        .after(GAME_TIME_UPDATE),
+                ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::InGame)
+                    .with_system(start_run_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::MainMenu)
+                    .with_system(leave_run_system.before(UNLOAD_LEVEL)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::LoadingLevel)
+                    .with_system(leave_run_system.before(UNLOAD_LEVEL)),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::MainMenu)
+                    .with_system(start_playback_system),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "goo-replay-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn recorded() -> Replay {
+        let mut replay = Replay::new(
+            42,
+            GameMode::Campaign,
+            "levels/level1.level.json".to_string(),
+            DifficultySettings::default(),
+        );
+        replay.frame_deltas = vec![16_667, 16_666, 33_000];
+        replay.inputs = vec![
+            TickInput {
+                tick: 0,
+                input: PlayerInput {
+                    movement: [1., -1.],
+                    cursor: Some([12.5, -3.]),
+                    select: true,
+                    add_to_selection: true,
+                    ..Default::default()
+                },
+            },
+            TickInput {
+                tick: 2,
+                input: PlayerInput {
+                    order: true,
+                    switch_formation: true,
+                    unmerge: true,
+                    ..Default::default()
+                },
+            },
+        ];
+        replay.summary = Some(ReplaySummary {
+            bloodrock: 7,
+            wave_index: 1,
+            outcome: Some(LevelOutcome::Win),
+        });
+        replay
+    }
+
+    #[test]
+    fn replay_round_trips_through_a_file() {
+        let replay = recorded();
+        let path = temp_file("round-trip.json");
+        std::fs::write(&path, serde_json::to_vec(&replay).unwrap()).unwrap();
+        let loaded = Replay::from_file(&path);
+        std::fs::remove_file(&path).ok();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.version, REPLAY_VERSION);
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.level, replay.level);
+        assert_eq!(loaded.frame_deltas, replay.frame_deltas);
+        assert_eq!(loaded.summary, replay.summary);
+        assert_eq!(loaded.inputs.len(), replay.inputs.len());
+        for (loaded, recorded) in loaded.inputs.iter().zip(&replay.inputs) {
+            assert_eq!(loaded.tick, recorded.tick);
+            assert_eq!(loaded.input, recorded.input);
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut replay = recorded();
+        replay.version = REPLAY_VERSION - 1;
+        let path = temp_file("old-version.json");
+        std::fs::write(&path, serde_json::to_vec(&replay).unwrap()).unwrap();
+        let err = Replay::from_file(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.unwrap_err().contains(&format!(
+            "replay version {} is not supported",
+            REPLAY_VERSION - 1
+        )));
+    }
+
+    #[test]
+    fn playback_feeds_inputs_on_their_ticks() {
+        let mut state = ReplayState::play(recorded());
+        assert_eq!(state.next_recorded_input().movement, [1., -1.]);
+        state.tick = 1;
+        assert_eq!(state.next_recorded_input(), PlayerInput::default());
+        state.tick = 2;
+        assert!(state.next_recorded_input().switch_formation);
+    }
+}
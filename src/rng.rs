@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::SceneState;
+
+// Keeps the cosmetic stream from mirroring the gameplay one
+const COSMETIC_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Every random decision goes through this resource, so a run can be replayed
+/// from its seed.
+///
+/// Anything that can change the outcome of a run (spawn positions, enemy
+/// picks, drops...) has to draw from `gameplay`. Particles, tips and other
+/// visuals draw from `cosmetic`, so turning them on or off does not change
+/// the gameplay stream.
+pub struct GameRng {
+    seed: u64,
+    /// When set, every run uses the same seed instead of rolling a new one
+    fixed_seed: bool,
+    pub gameplay: StdRng,
+    pub cosmetic: StdRng,
+}
+
+impl GameRng {
+    /// Every run will use `seed`
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            fixed_seed: true,
+            gameplay: StdRng::seed_from_u64(seed),
+            cosmetic: StdRng::seed_from_u64(seed ^ COSMETIC_SEED_SALT),
+        }
+    }
+
+    /// Rolls a new seed for every run
+    pub fn random() -> Self {
+        let mut rng = GameRng::from_seed(rand::thread_rng().gen());
+        rng.fixed_seed = false;
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts both streams from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.gameplay = StdRng::seed_from_u64(seed);
+        self.cosmetic = StdRng::seed_from_u64(seed ^ COSMETIC_SEED_SALT);
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::random()
+    }
+}
+
+fn start_run_system(mut rng: ResMut<GameRng>) {
+    let seed = if rng.fixed_seed {
+        rng.seed
+    } else {
+        rand::thread_rng().gen()
+    };
+    rng.reseed(seed);
+    info!("Starting run with seed {}", seed);
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameRng>().add_system_set(
+            SystemSet::on_enter(SceneState::LoadingLevel)
+                .with_system(start_run_system),
+        );
+    }
+}
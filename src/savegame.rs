@@ -0,0 +1,408 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    campaign::{select_level, Campaign},
+    elite::{Affix, Elite},
+    endless::EndlessRun,
+    enemy_archetype::EnemyArchetypes,
+    enemy_logic::{
+        spawn_enemy, BasicEnemyLogic, EnemyId, EnemyKind, EnemyStatScale,
+        LevelManager,
+    },
+    game::{
+        spawn_bloodrock_node, spawn_unit_with_class, BloodrockAmount,
+        BloodrockNode, GameAssets, MaxSupplyAmount, PlayerController,
+        ResourceAssets,
+    },
+    health::{hp_material, Health},
+    merge_recipes::{ClassId, MergeRecipes, MergedFrom, TierClass},
+    objectives::{Objectives, SavedObjective},
+    raider::{Raider, SavedRaider},
+    replay::ReplayState,
+    storage,
+    worker_logic::{UnitClass, UnitFollowPlayer, UnitSize},
+    GameMode, SceneState,
+};
+
+const SAVE_FILE: &str = "savegame.json";
+
+/// Bump this whenever the save format changes, older saves get ignored
+pub const SAVE_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedAlly {
+    pub class: UnitClass,
+    pub size: UnitSize,
+    /// `class` is its base class then
+    #[serde(default)]
+    pub tier_class: Option<ClassId>,
+    pub health: Health,
+    pub position: [f32; 2],
+    /// Units grow as they eat each other
+    pub scale: f32,
+    /// What it splits back into
+    #[serde(default)]
+    pub merged_from: Option<MergedFrom>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedEnemy {
+    pub kind: EnemyId,
+    pub stat_scale: EnemyStatScale,
+    pub health: Health,
+    pub position: [f32; 2],
+    /// Empty for regular enemies
+    #[serde(default)]
+    pub affixes: Vec<Affix>,
+    /// Stolen bloodrock stays on the raider
+    #[serde(default)]
+    pub raider: Option<SavedRaider>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedNode {
+    pub amount_of_resource: usize,
+    pub position: [f32; 2],
+}
+
+/// A level in progress
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub game_mode: GameMode,
+    /// Index into the campaign levels, unused in endless mode
+    pub campaign_level: usize,
+    pub wave_index: usize,
+    /// Seconds already waited for the current wave
+    pub wave_timer_elapsed: f32,
+    /// Survived seconds in endless mode
+    pub endless_elapsed: f32,
+    pub bloodrock: usize,
+    pub max_supply: usize,
+    pub player_position: [f32; 2],
+    pub player_health: Health,
+    pub allies: Vec<SavedAlly>,
+    pub enemies: Vec<SavedEnemy>,
+    pub nodes: Vec<SavedNode>,
+    pub objectives: Vec<SavedObjective>,
+}
+
+/// The save on disk, if there is one
+#[derive(Default)]
+pub struct SaveSlot {
+    pub save: Option<SaveGame>,
+    /// The save gets applied once the level is spawned
+    restore_pending: bool,
+}
+
+/// Saves the level in progress
+pub struct SaveGameEvent;
+
+/// Loads the level of the save, then restores it
+pub struct ContinueGameEvent;
+
+fn load_save_slot(mut slot: ResMut<SaveSlot>) {
+    match storage::load::<SaveGame>(SAVE_FILE) {
+        Ok(Some(save)) if save.version == SAVE_VERSION => {
+            slot.save = Some(save)
+        }
+        Ok(Some(save)) => warn!(
+            "Ignoring save of version {}, expected {}",
+            save.version, SAVE_VERSION
+        ),
+        Ok(None) => {}
+        Err(err) => warn!("Failed to load save: {}", err),
+    }
+}
+
+fn save_game_system(
+    mut events: EventReader<SaveGameEvent>,
+    game_mode: Res<GameMode>,
+    campaign: Res<Campaign>,
+    level_manager: Res<LevelManager>,
+    bloodrock: Res<BloodrockAmount>,
+    max_supply: Res<MaxSupplyAmount>,
+    endless_run: Res<EndlessRun>,
+    player: Query<(&Transform, &Health), With<PlayerController>>,
+    allies: Query<
+        (
+            &Transform,
+            &Health,
+            &UnitClass,
+            &UnitSize,
+            Option<&TierClass>,
+            Option<&MergedFrom>,
+        ),
+        With<UnitFollowPlayer>,
+    >,
+    enemies: Query<
+        (
+            &Transform,
+            &Health,
+            &EnemyKind,
+            Option<&Elite>,
+            Option<&Raider>,
+        ),
+        With<BasicEnemyLogic>,
+    >,
+    nodes: Query<(&Transform, &BloodrockNode)>,
+    objectives: Res<Objectives>,
+    mut slot: ResMut<SaveSlot>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    let (player_tr, player_health) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => {
+            warn!("Nothing to save, the player is not alive");
+            return;
+        }
+    };
+
+    let level = &level_manager.current_level;
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        game_mode: *game_mode,
+        campaign_level: campaign.current,
+        wave_index: level.current_wave_index,
+        wave_timer_elapsed: level
+            .waves
+            .get(level.current_wave_index)
+            .map(|wave| {
+                wave.time_to_spawn_after_last_wave.elapsed().as_secs_f32()
+            })
+            .unwrap_or_default(),
+        endless_elapsed: endless_run.elapsed,
+        bloodrock: bloodrock.0,
+        max_supply: max_supply.0,
+        player_position: player_tr.translation.truncate().into(),
+        player_health: *player_health,
+        allies: allies
+            .iter()
+            .map(|(tr, health, class, size, tier_class, merged_from)| {
+                SavedAlly {
+                    class: *class,
+                    size: *size,
+                    tier_class: tier_class
+                        .map(|tier_class| tier_class.id.clone()),
+                    health: *health,
+                    position: tr.translation.truncate().into(),
+                    scale: tr.scale.x,
+                    merged_from: merged_from.cloned(),
+                }
+            })
+            .collect(),
+        enemies: enemies
+            .iter()
+            .map(|(tr, health, kind, elite, raider)| SavedEnemy {
+                kind: kind.kind.clone(),
+                stat_scale: kind.stat_scale,
+                health: *health,
+                position: tr.translation.truncate().into(),
+                affixes: elite
+                    .map(|elite| elite.affixes.clone())
+                    .unwrap_or_default(),
+                raider: raider.map(Raider::save),
+            })
+            .collect(),
+        nodes: nodes
+            .iter()
+            .map(|(tr, node)| SavedNode {
+                amount_of_resource: node.amount_of_resource,
+                position: tr.translation.truncate().into(),
+            })
+            .collect(),
+        objectives: objectives.save(),
+    };
+
+    match storage::save(SAVE_FILE, &save) {
+        Ok(()) => {
+            info!("Game saved");
+            slot.save = Some(save);
+        }
+        Err(err) => warn!("Failed to save the game: {}", err),
+    }
+}
+
+fn continue_game_system(
+    mut events: EventReader<ContinueGameEvent>,
+    mut slot: ResMut<SaveSlot>,
+    mut game_mode: ResMut<GameMode>,
+    mut campaign: ResMut<Campaign>,
+    mut level_manager: ResMut<LevelManager>,
+    mut app_state: ResMut<State<SceneState>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    let save = match &slot.save {
+        Some(save) => save,
+        None => return,
+    };
+    if save.game_mode == GameMode::Campaign {
+        if save.campaign_level >= campaign.levels.len() {
+            warn!("Save points to a level that no longer exists");
+            return;
+        }
+        select_level(save.campaign_level, &mut campaign, &mut level_manager);
+    }
+    *game_mode = save.game_mode;
+    slot.restore_pending = true;
+    app_state.set(SceneState::LoadingLevel).unwrap_or_default();
+}
+
+/// Replaces the freshly spawned level with the one in the save
+fn restore_game_system(
+    mut cmd: Commands,
+    mut slot: ResMut<SaveSlot>,
+    game_assets: Res<GameAssets>,
+    resource_assets: Res<ResourceAssets>,
+    archetypes: Res<EnemyArchetypes>,
+    recipes: Res<MergeRecipes>,
+    mut hp_assets: ResMut<Assets<hp_material::HpMaterial>>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut bloodrock: ResMut<BloodrockAmount>,
+    mut max_supply: ResMut<MaxSupplyAmount>,
+    mut level_manager: ResMut<LevelManager>,
+    mut endless_run: ResMut<EndlessRun>,
+    mut replay: ResMut<ReplayState>,
+    mut objectives: ResMut<Objectives>,
+    mut player: Query<(&mut Transform, &mut Health), With<PlayerController>>,
+    spawned: Query<
+        Entity,
+        Or<(
+            With<UnitFollowPlayer>,
+            With<BasicEnemyLogic>,
+            With<BloodrockNode>,
+        )>,
+    >,
+) {
+    if !slot.restore_pending {
+        return;
+    }
+    // wait for the level to spawn its default units first
+    let (mut player_tr, mut player_health) = match player.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    slot.restore_pending = false;
+    let save = match &slot.save {
+        Some(save) => save,
+        None => return,
+    };
+
+    for entity in spawned.iter() {
+        cmd.entity(entity).despawn_recursive();
+    }
+
+    player_tr.translation =
+        Vec2::from(save.player_position).extend(player_tr.translation.z);
+    *player_health = save.player_health;
+    bloodrock.0 = save.bloodrock;
+    max_supply.0 = save.max_supply;
+    endless_run.elapsed = save.endless_elapsed;
+
+    let level = &mut level_manager.current_level;
+    level.current_wave_index = save.wave_index;
+    if let Some(wave) = level.waves.get_mut(save.wave_index) {
+        wave.time_to_spawn_after_last_wave
+            .set_elapsed(Duration::from_secs_f32(save.wave_timer_elapsed));
+    }
+
+    for ally in save.allies.iter() {
+        let entity = spawn_unit_with_class(
+            &mut cmd,
+            &game_assets,
+            &resource_assets,
+            Vec2::from(ally.position).extend(0.),
+            ally.class,
+            &mut *hp_assets,
+        );
+        let mut tr =
+            Transform::from_translation(Vec2::from(ally.position).extend(0.));
+        tr.scale = Vec3::splat(ally.scale);
+        cmd.entity(entity)
+            .insert(tr)
+            .insert(ally.size)
+            .insert(ally.health);
+        // the saved health already has the tier class multiplier
+        if let Some((_, Some(tier_class))) =
+            ally.tier_class.as_ref().and_then(|id| recipes.resolve(id))
+        {
+            cmd.entity(entity).insert(tier_class);
+        }
+        if let Some(merged_from) = &ally.merged_from {
+            cmd.entity(entity).insert(merged_from.clone());
+        }
+    }
+    for enemy in save.enemies.iter() {
+        let entity = spawn_enemy(
+            &enemy.kind,
+            &mut cmd,
+            &archetypes,
+            Vec2::from(enemy.position).extend(0.),
+            &mut *hp_assets,
+            &mut *mesh_assets,
+            &resource_assets,
+            &enemy.stat_scale,
+        );
+        if let Some(entity) = entity {
+            cmd.entity(entity).insert(enemy.health);
+            if !enemy.affixes.is_empty() {
+                cmd.entity(entity)
+                    .insert(Elite::restored(enemy.affixes.clone()));
+            }
+            if let (Some(saved), Some(desc)) = (
+                &enemy.raider,
+                archetypes
+                    .get(&enemy.kind)
+                    .and_then(|archetype| archetype.raider.as_ref()),
+            ) {
+                // replaces the fresh raider spawn_enemy inserted
+                cmd.entity(entity).insert(Raider::restored(desc, saved));
+            }
+        }
+    }
+    for node in save.nodes.iter() {
+        let entity = spawn_bloodrock_node(
+            &mut cmd,
+            &resource_assets,
+            Vec2::from(node.position).extend(0.),
+        );
+        cmd.entity(entity).insert(BloodrockNode {
+            amount_of_resource: node.amount_of_resource,
+        });
+    }
+
+    objectives.restore(&save.objectives);
+
+    // the recording started from a fresh level, it could not be played back
+    replay.discard_run();
+    info!("Restored saved game");
+}
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlot>()
+            .add_event::<SaveGameEvent>()
+            .add_event::<ContinueGameEvent>()
+            .add_startup_system(load_save_slot)
+            .add_system_set(
+                SystemSet::on_update(SceneState::Paused)
+                    .with_system(save_game_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::MainMenu)
+                    .with_system(continue_game_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::InGame)
+                    .with_system(restore_game_system),
+            );
+    }
+}
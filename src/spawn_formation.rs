@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::CombatComponent,
+    enemy_logic::{Arena, BasicEnemyLogic, EnemyId},
+    game::Velocity,
+    worker_logic::HealerComponent,
+    GameTime, SceneState,
+};
+
+/// How far outside of the arena edge groups entering from it are spawned
+const EDGE_SPAWN_MARGIN: f32 = 150.;
+
+/// Placement of the enemies of a spawn group around its position
+///
+/// Formations face the center of the arena, front line enemies (armored,
+/// melee) take the front slots, ranged enemies and healers the ones behind.
+/// The archetype file ranks them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Formation {
+    /// Random points on a circle of `radius` around the position
+    Scattered {
+        #[serde(default = "default_scatter_radius")]
+        radius: f32,
+    },
+    /// Side by side
+    Line {
+        #[serde(default = "default_spacing")]
+        spacing: f32,
+    },
+    /// A single enemy at the tip, the rest in rows behind it
+    Wedge {
+        #[serde(default = "default_spacing")]
+        spacing: f32,
+    },
+    /// Evenly spaced on a circle of `radius`
+    Ring {
+        #[serde(default = "default_ring_radius")]
+        radius: f32,
+    },
+    /// One behind the other
+    Column {
+        #[serde(default = "default_spacing")]
+        spacing: f32,
+    },
+}
+
+fn default_scatter_radius() -> f32 {
+    500.
+}
+
+fn default_spacing() -> f32 {
+    100.
+}
+
+fn default_ring_radius() -> f32 {
+    150.
+}
+
+impl Default for Formation {
+    fn default() -> Self {
+        Formation::Scattered {
+            radius: default_scatter_radius(),
+        }
+    }
+}
+
+/// Side of the arena a spawn group marches in from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArenaEdge {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl ArenaEdge {
+    /// Points out of the arena
+    fn normal(self) -> Vec2 {
+        match self {
+            ArenaEdge::North => Vec2::Y,
+            ArenaEdge::South => Vec2::NEG_Y,
+            ArenaEdge::East => Vec2::X,
+            ArenaEdge::West => Vec2::NEG_X,
+        }
+    }
+
+    /// Distance of the edge from the origin along `normal`
+    fn distance(self, arena: &Arena) -> f32 {
+        match self {
+            ArenaEdge::North => arena.max.y,
+            ArenaEdge::South => -arena.min.y,
+            ArenaEdge::East => arena.max.x,
+            ArenaEdge::West => -arena.min.x,
+        }
+    }
+}
+
+/// Walks to `target` keeping its place in the formation, enemies do not pick
+/// targets until they arrive or something picks a fight with them
+#[derive(Component)]
+pub struct MarchIn {
+    pub target: Vec3,
+}
+
+/// Where each enemy of a spawn group ends up, and where it is spawned
+pub struct FormationSlot {
+    pub enemy: EnemyId,
+    pub position: Vec3,
+    /// Set if the group enters from an arena edge
+    pub spawn_position: Option<Vec3>,
+}
+
+pub fn formation_slots(
+    enemies: &[EnemyId],
+    position: Vec2,
+    formation: Formation,
+    entry: Option<ArenaEdge>,
+    arena: &Arena,
+    // lower ranks stand in front
+    rank: impl Fn(&EnemyId) -> u8,
+    rng: &mut impl Rng,
+) -> Vec<FormationSlot> {
+    let forward = match entry {
+        Some(edge) => -edge.normal(),
+        None => ((arena.min + arena.max) / 2. - position)
+            .try_normalize()
+            .unwrap_or(Vec2::NEG_Y),
+    };
+    let right = forward.perp();
+
+    let mut enemies = enemies.to_vec();
+    if !matches!(formation, Formation::Scattered { .. }) {
+        enemies.sort_by_key(&rank);
+    }
+    let count = enemies.len();
+    let centered = |i: usize| i as f32 - (count as f32 - 1.) / 2.;
+
+    let offsets: Vec<Vec2> = (0..count)
+        .map(|i| match formation {
+            Formation::Scattered { radius } => {
+                Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0))
+                    .normalize_or_zero()
+                    * radius
+            }
+            Formation::Line { spacing } => right * centered(i) * spacing,
+            Formation::Column { spacing } => -forward * centered(i) * spacing,
+            Formation::Wedge { spacing } => {
+                let row = ((i + 1) / 2) as f32;
+                let side = if i % 2 == 0 { 1. } else { -1. };
+                (right * side - forward) * row * spacing
+            }
+            Formation::Ring { radius } => {
+                let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+                Vec2::new(angle.cos(), angle.sin()) * radius
+            }
+        })
+        .collect();
+
+    // push the whole group out of the arena, keeping its shape
+    let entry_shift = entry.map(|edge| {
+        let normal = edge.normal();
+        let innermost = offsets
+            .iter()
+            .map(|offset| (position + *offset).dot(normal))
+            .fold(f32::INFINITY, f32::min);
+        normal * (edge.distance(arena) + EDGE_SPAWN_MARGIN - innermost).max(0.)
+    });
+
+    enemies
+        .into_iter()
+        .zip(offsets)
+        .map(|(enemy, offset)| {
+            let slot = position + offset;
+            FormationSlot {
+                enemy,
+                position: slot.extend(0.),
+                spawn_position: entry_shift
+                    .map(|shift| (slot + shift).extend(0.)),
+            }
+        })
+        .collect()
+}
+
+fn march_in_system(
+    mut cmd: Commands,
+    time: Res<GameTime>,
+    mut marching: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Velocity,
+            &MarchIn,
+            Option<&CombatComponent>,
+            Option<&HealerComponent>,
+        ),
+        With<BasicEnemyLogic>,
+    >,
+) {
+    for (entity, mut tr, vel, march, combat, healer) in marching.iter_mut() {
+        // taunted, or healing someone, the formation breaks up
+        if combat.map_or(false, |combat| combat.target.is_some())
+            || healer.map_or(false, |healer| healer.target.is_some())
+        {
+            cmd.entity(entity).remove::<MarchIn>();
+            continue;
+        }
+        let to_target = (march.target - tr.translation).truncate();
+        let step = vel.0 * time.delta_seconds();
+        if to_target.length() <= step {
+            tr.translation = march.target.truncate().extend(tr.translation.z);
+            cmd.entity(entity).remove::<MarchIn>();
+        } else {
+            tr.translation += (to_target.normalize() * step).extend(0.);
+        }
+    }
+}
+
+pub struct SpawnFormationPlugin;
+
+impl Plugin for SpawnFormationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                .with_system(march_in_system),
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Everything we persist between runs lives in this directory, next to the
+/// working directory of the launcher
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_DIR: &str = "saves";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_path(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(SAVE_DIR).join(name)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    std::fs::create_dir_all(SAVE_DIR).map_err(|err| err.to_string())?;
+    let json =
+        serde_json::to_string_pretty(value).map_err(|err| err.to_string())?;
+    std::fs::write(save_path(name), json).map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load<T: DeserializeOwned>(name: &str) -> Result<Option<T>, String> {
+    match std::fs::read(save_path(name)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|err| format!("{}: {}", name, err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+// TODO: use local storage on the web
+#[cfg(target_arch = "wasm32")]
+pub fn save<T: Serialize>(_name: &str, _value: &T) -> Result<(), String> {
+    Err("saving is not supported on the web yet".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load<T: DeserializeOwned>(_name: &str) -> Result<Option<T>, String> {
+    Ok(None)
+}
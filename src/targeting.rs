@@ -0,0 +1,227 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{tank_aggro_component, AttackState, CombatComponent, Taunted},
+    enemy_logic::BasicEnemyLogic,
+    game::{Harvester, PlayerController, UnitType},
+    health::Health,
+    objectives::ProtectedStructure,
+    orders::UnitOrder,
+    spawn_formation::MarchIn,
+    worker_logic::{HealerComponent, TankComponent, UnitFollowPlayer},
+    GameTime, SceneState,
+};
+
+/// Seconds between two target evaluations of the same unit
+const RETARGET_INTERVAL: f32 = 0.5;
+/// A new target has to score this much better than the current one
+const RETARGET_HYSTERESIS: f32 = 0.7;
+/// Score multiplier of targets a policy prefers
+const PREFERRED: f32 = 0.3;
+/// Allies give up a target this far past their acquire range, so they do
+/// not chase it across the map nor drop it the moment it steps out of range
+const DROP_TARGET_MARGIN: f32 = 100.;
+
+/// How a unit picks what to attack, every policy still favors close targets
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
+pub enum TargetPolicy {
+    #[default]
+    Nearest,
+    LowestHealth,
+    /// Whatever deals the most damage per second
+    HighestThreat,
+    PreferHealers,
+    PreferWorkers,
+    /// Armor or taunting tanks, piercing attacks go through armor
+    PreferArmored,
+}
+
+#[derive(Component)]
+pub struct Targeting {
+    pub policy: TargetPolicy,
+    retarget: Timer,
+}
+
+impl Targeting {
+    pub fn new(policy: TargetPolicy) -> Self {
+        Targeting {
+            policy,
+            retarget: Timer::from_seconds(RETARGET_INTERVAL, true),
+        }
+    }
+}
+
+struct Candidate {
+    entity: Entity,
+    position: Vec2,
+    health: f32,
+    armored: bool,
+    healer: bool,
+    worker: bool,
+    enemy: bool,
+}
+
+impl TargetPolicy {
+    /// Lower is better
+    fn score(&self, candidate: &Candidate, distance: f32, threat: f32) -> f32 {
+        let multiplier = match self {
+            TargetPolicy::Nearest => 1.,
+            TargetPolicy::LowestHealth => 0.25 + 0.75 * candidate.health,
+            TargetPolicy::HighestThreat => 1. / (1. + threat),
+            TargetPolicy::PreferHealers if candidate.healer => PREFERRED,
+            TargetPolicy::PreferWorkers if candidate.worker => PREFERRED,
+            TargetPolicy::PreferArmored if candidate.armored => PREFERRED,
+            _ => 1.,
+        };
+        distance * multiplier
+    }
+}
+
+/// Allies only look for enemies close by, enemies go for anything
+fn acquire_range(combat: &CombatComponent) -> f32 {
+    match combat.target_type {
+        UnitType::Enemy => combat.attack_range.max(200.) + 100.,
+        UnitType::Ally => f32::INFINITY,
+    }
+}
+
+fn targeting_system(
+    time: Res<GameTime>,
+    mut units: ParamSet<(
+        Query<
+            (
+                Entity,
+                &mut Targeting,
+                &mut CombatComponent,
+                &GlobalTransform,
+            ),
+            // ordered units attack what they were told to, taunted ones
+            // stick to the tank
+            (Without<MarchIn>, Without<UnitOrder>, Without<Taunted>),
+        >,
+        // everything that fights is a threat, ordered or not
+        Query<(Entity, &CombatComponent)>,
+    )>,
+    candidates: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&Health>,
+            Option<&HealerComponent>,
+            Option<&Harvester>,
+            Option<&TankComponent>,
+            Option<&BasicEnemyLogic>,
+        ),
+        Or<(
+            With<BasicEnemyLogic>,
+            With<UnitFollowPlayer>,
+            With<ProtectedStructure>,
+        )>,
+    >,
+    player: Query<Entity, With<PlayerController>>,
+) {
+    let threats: HashMap<Entity, f32> = units
+        .p1()
+        .iter()
+        .map(|(entity, combat)| {
+            let seconds = combat.time_between_attacks.duration().as_secs_f32();
+            (entity, combat.damage / seconds.max(0.1))
+        })
+        .collect();
+    let candidates: Vec<Candidate> = candidates
+        .iter()
+        .map(
+            |(entity, tr, health, healer, harvester, tank, enemy)| Candidate {
+                entity,
+                position: tr.translation().truncate(),
+                health: health.map_or(1., |health| {
+                    health.current_health / health.max_health
+                }),
+                armored: tank.is_some()
+                    || health.map_or(false, |health| health.armor > 0.),
+                healer: healer.is_some(),
+                worker: harvester.is_some(),
+                enemy: enemy.is_some(),
+            },
+        )
+        .collect();
+
+    for (entity, mut targeting, mut combat, tr) in units.p0().iter_mut() {
+        targeting.retarget.tick(time.delta());
+        if combat.target.is_some() && !targeting.retarget.just_finished() {
+            continue;
+        }
+        // switching mid swing would hit the new target
+        if !matches!(combat.attack_state, AttackState::NotAttacking) {
+            continue;
+        }
+        let position = tr.translation().truncate();
+        let wants_enemies = matches!(combat.target_type, UnitType::Enemy);
+        let range = acquire_range(&combat);
+        let score = |candidate: &Candidate| {
+            let distance = (candidate.position - position).length();
+            let threat = threats.get(&candidate.entity).copied().unwrap_or(0.);
+            targeting.policy.score(candidate, distance, threat)
+        };
+
+        let best = candidates
+            .iter()
+            .filter(|candidate| {
+                candidate.entity != entity
+                    && candidate.enemy == wants_enemies
+                    && (candidate.position - position).length() <= range
+            })
+            .map(|candidate| (candidate.entity, score(candidate)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let current = combat.target.and_then(|target| {
+            candidates
+                .iter()
+                .find(|candidate| candidate.entity == target)
+                .map(score)
+        });
+
+        match (best, current) {
+            (Some((best, best_score)), Some(current_score)) => {
+                if best_score < current_score * RETARGET_HYSTERESIS {
+                    combat.target = Some(best);
+                }
+            }
+            (Some((best, _)), None) => combat.target = Some(best),
+            (None, _) if wants_enemies => {
+                let out_of_range = combat.target.map_or(false, |target| {
+                    candidates
+                        .iter()
+                        .find(|candidate| candidate.entity == target)
+                        .map_or(false, |candidate| {
+                            (candidate.position - position).length()
+                                > range + DROP_TARGET_MARGIN
+                        })
+                });
+                if out_of_range {
+                    combat.target = None;
+                }
+            }
+            (None, _) => {
+                // enemies with nothing else around go for the player
+                if combat.target.is_none() {
+                    combat.target = player.iter().next();
+                }
+            }
+        }
+    }
+}
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(SceneState::InGame)
+                // a taunt in the same frame wins over the policy
+                .with_system(targeting_system.before(tank_aggro_component)),
+        );
+    }
+}
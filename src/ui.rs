@@ -1,11 +1,21 @@
 use crate::{
     audio::Options,
+    boss::Boss,
+    campaign::{select_level, Campaign, CampaignProgress},
+    difficulty::{Difficulty, DifficultyPreset, MULTIPLIER_RANGE},
     easing::Easing,
+    endless::{format_survival_time, EndlessRecord, EndlessRun},
     enemy_logic::LevelManager,
-    game::{BloodrockAmount, MaxSupplyAmount},
+    game::{BloodrockAmount, MaxSupplyAmount, UNIT_COST},
+    health::Health,
     lerp::Lerp,
-    worker_logic::UnitFollowPlayer,
-    DontDestroyBetweenLevels, GameTime, SceneState,
+    level::LevelAsset,
+    loot::LootBuffs,
+    objectives::Objectives,
+    rng::GameRng,
+    savegame::{ContinueGameEvent, SaveGameEvent, SaveSlot},
+    worker_logic::{Formation, UnitFollowPlayer},
+    DontDestroyBetweenLevels, GameMode, GameTime, SceneState,
 };
 use bevy::prelude::*;
 pub struct UIPlugin;
@@ -25,9 +35,29 @@ pub struct SupplyText;
 #[derive(Component)]
 pub struct WaveText;
 
+#[derive(Component)]
+pub struct ObjectivesText;
+
+#[derive(Component)]
+pub struct BossBarNode;
+
+#[derive(Component)]
+pub struct BossBarFill;
+
+#[derive(Component)]
+pub struct BossNameText;
+
+#[derive(Component)]
+pub struct LootBuffsText;
+
+#[derive(Component)]
+pub struct FormationText;
+
 #[derive(PartialEq, Clone)]
 pub enum UIState {
     Options,
+    LevelSelect,
+    Difficulty,
     None,
 }
 
@@ -40,11 +70,91 @@ fn update_bloodrock_text(
     }
 }
 
+fn update_objectives_text(
+    mut objective_texts: Query<&mut Text, With<ObjectivesText>>,
+    objectives: Res<Objectives>,
+) {
+    for mut text in objective_texts.iter_mut() {
+        text.sections[0].value = objectives
+            .list
+            .iter()
+            .map(|objective| objective.describe())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+fn update_loot_buffs_text(
+    mut buff_texts: Query<&mut Text, With<LootBuffsText>>,
+    buffs: Res<LootBuffs>,
+) {
+    for mut text in buff_texts.iter_mut() {
+        text.sections[0].value = buffs
+            .active
+            .iter()
+            .map(|buff| match &buff.timer {
+                Some(timer) => format!(
+                    "{} x{:.1} ({:.0}s)",
+                    buff.name,
+                    buff.multiplier,
+                    timer.duration().as_secs_f32() - timer.elapsed_secs()
+                ),
+                None => format!("{} x{:.1}", buff.name, buff.multiplier),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+fn update_formation_text(
+    mut formation_texts: Query<&mut Text, With<FormationText>>,
+    formation: Res<Formation>,
+) {
+    for mut text in formation_texts.iter_mut() {
+        text.sections[0].value =
+            format!("Formation: {}", formation.kind.name());
+    }
+}
+
+/// Shows the health of the first boss alive across the top of the screen
+fn update_boss_bar_system(
+    bosses: Query<(&Boss, &Health)>,
+    mut bar_nodes: Query<&mut Style, With<BossBarNode>>,
+    mut bar_fills: Query<&mut Style, (With<BossBarFill>, Without<BossBarNode>)>,
+    mut name_texts: Query<&mut Text, With<BossNameText>>,
+) {
+    let boss = bosses.iter().next();
+    for mut style in bar_nodes.iter_mut() {
+        style.display = if boss.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    let (boss, health) = match boss {
+        Some(boss) => boss,
+        None => return,
+    };
+    let fraction = (health.current_health / health.max_health).clamp(0., 1.);
+    for mut style in bar_fills.iter_mut() {
+        style.size.width = Val::Percent(fraction * 100.);
+    }
+    for mut text in name_texts.iter_mut() {
+        text.sections[0].value = match boss.phase {
+            Some(phase) => format!("{} - Phase {}", boss.name, phase + 1),
+            None => boss.name.clone(),
+        };
+    }
+}
+
 fn update_supply_text(
     mut supply_texts: Query<&mut Text, With<SupplyText>>,
     mut wave_texts: Query<&mut Text, (With<WaveText>, Without<SupplyText>)>,
     level_manager: Res<LevelManager>,
     max_supply: Res<MaxSupplyAmount>,
+    game_mode: Res<GameMode>,
+    endless_run: Res<EndlessRun>,
+    endless_record: Res<EndlessRecord>,
 
     workers: Query<Entity, With<UnitFollowPlayer>>,
 ) {
@@ -54,11 +164,35 @@ fn update_supply_text(
     }
 
     for mut text in wave_texts.iter_mut() {
-        text.sections[0].value = format!(
-            "Wave: {} / {}",
-            level_manager.current_level.current_wave_index,
-            level_manager.current_level.waves.len()
-        );
+        text.sections[0].value = match *game_mode {
+            GameMode::Campaign => {
+                let level = &level_manager.current_level;
+                let early_call_bonus = level
+                    .waves
+                    .get(level.current_wave_index)
+                    .and_then(|wave| wave.early_call_bonus);
+                match early_call_bonus {
+                    Some(bonus) => format!(
+                        "Wave: {} / {} - N: call early (+{})",
+                        level.current_wave_index,
+                        level.waves.len(),
+                        bonus
+                    ),
+                    None => format!(
+                        "Wave: {} / {}",
+                        level.current_wave_index,
+                        level.waves.len()
+                    ),
+                }
+            }
+            GameMode::Endless => format!(
+                "Survived: {} - Best: {}",
+                format_survival_time(endless_run.elapsed),
+                format_survival_time(
+                    endless_record.best_time.max(endless_run.elapsed)
+                )
+            ),
+        };
     }
 }
 #[derive(Component)]
@@ -72,9 +206,12 @@ fn setup_in_game_ui(
     asset_server: Res<AssetServer>,
     root_node: Query<Entity, With<RootNode>>,
     mut losing_manager: ResMut<EndGameManager>,
+    difficulty: Res<Difficulty>,
 ) {
+    let unit_cost = difficulty.settings().unit_cost(UNIT_COST);
     losing_manager.state = EndGameState::NotEndGame;
     losing_manager.time_to_fade_in = Timer::from_seconds(1., false);
+    losing_manager.objectives.clear();
     for node in root_node.iter() {
         commands.entity(node).with_children(|cmd| {
             //Main node
@@ -225,6 +362,94 @@ fn setup_in_game_ui(
                             ))
                             .insert(BloodrockText);
                     });
+                child
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                right: Val::Auto,
+                                left: Val::Px(10.),
+                                top: Val::Px(80.),
+                                bottom: Val::Auto,
+                            },
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .with_children(|child| {
+                        child
+                            .spawn_bundle(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server
+                                        .load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 22.0,
+                                    color: Color::WHITE,
+                                },
+                            ))
+                            .insert(ObjectivesText);
+                    });
+                child
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            display: Display::None,
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                right: Val::Auto,
+                                left: Val::Percent(30.),
+                                top: Val::Px(10.),
+                                bottom: Val::Auto,
+                            },
+                            size: Size::new(Val::Percent(40.0), Val::Auto),
+                            flex_direction: FlexDirection::ColumnReverse,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .insert(BossBarNode)
+                    .with_children(|child| {
+                        child
+                            .spawn_bundle(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server
+                                        .load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 26.0,
+                                    color: Color::WHITE,
+                                },
+                            ))
+                            .insert(BossNameText);
+                        child
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(
+                                        Val::Percent(100.0),
+                                        Val::Px(20.0),
+                                    ),
+                                    ..Default::default()
+                                },
+                                color: UiColor(Color::rgba(0.2, 0., 0., 0.8)),
+                                ..Default::default()
+                            })
+                            .with_children(|child| {
+                                child
+                                    .spawn_bundle(NodeBundle {
+                                        style: Style {
+                                            size: Size::new(
+                                                Val::Percent(100.0),
+                                                Val::Percent(100.0),
+                                            ),
+                                            ..Default::default()
+                                        },
+                                        color: UiColor(Color::ORANGE_RED),
+                                        ..Default::default()
+                                    })
+                                    .insert(BossBarFill);
+                            });
+                    });
                 child
                     .spawn_bundle(NodeBundle {
                         style: Style {
@@ -254,6 +479,35 @@ fn setup_in_game_ui(
                             ))
                             .insert(SupplyText);
                     });
+                child
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                right: Val::Px(0.),
+                                left: Val::Auto,
+                                top: Val::Px(40.),
+                                bottom: Val::Auto,
+                            },
+                            size: Size::new(Val::Px(400.0), Val::Auto),
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .with_children(|child| {
+                        child
+                            .spawn_bundle(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server
+                                        .load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 22.0,
+                                    color: Color::rgb(1., 0.8, 0.3),
+                                },
+                            ))
+                            .insert(LootBuffsText);
+                    });
 
                 child
                     .spawn_bundle(NodeBundle {
@@ -305,10 +559,14 @@ fn setup_in_game_ui(
                     })
                     .with_children(|child| {
                         child.spawn_bundle(TextBundle::from_section(
-                            "WASD to move
+                            format!(
+                                "WASD to move
 Drag and Drop units to combine them
-'SPACE' - spawn new unit - Cost: 10
-'F' - deal damage around self - Cost: 3",
+'SPACE' - spawn new unit - Cost: {}
+'F' - deal damage around self - Cost: 3
+'G' - switch formation",
+                                unit_cost
+                            ),
                             TextStyle {
                                 font: asset_server
                                     .load("fonts/FiraSans-Bold.ttf"),
@@ -317,6 +575,34 @@ Drag and Drop units to combine them
                             },
                         ));
                     });
+                child
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Auto,
+                                right: Val::Px(40.),
+                                bottom: Val::Px(40.),
+                                top: Val::Auto,
+                            },
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .with_children(|child| {
+                        child
+                            .spawn_bundle(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server
+                                        .load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 30.0,
+                                    color: Color::WHITE,
+                                },
+                            ))
+                            .insert(FormationText);
+                    });
             });
         });
     }
@@ -337,6 +623,10 @@ fn main_menu_logic(
     mut exit: EventWriter<AppExit>,
     mut ui_state: ResMut<UIState>,
     mut app_state: ResMut<State<SceneState>>,
+    mut game_mode: ResMut<GameMode>,
+    save_slot: Res<SaveSlot>,
+    mut continue_game: EventWriter<ContinueGameEvent>,
+    difficulty: Res<Difficulty>,
 ) {
     match *ui_state {
         UIState::None => {}
@@ -379,6 +669,19 @@ fn main_menu_logic(
                 UIState::None => {
                     ui.vertical_centered(|ui| {
                         ui.add_space(20.);
+                        if save_slot.save.is_some() {
+                            if ui
+                                .add_sized(
+                                    [220.0, 80.0],
+                                    egui::Button::new("Continue"),
+                                )
+                                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .clicked()
+                            {
+                                continue_game.send(ContinueGameEvent);
+                            }
+                            ui.add_space(20.);
+                        }
                         if ui
                             .add_sized(
                                 [220.0, 80.0],
@@ -387,10 +690,36 @@ fn main_menu_logic(
                             .on_hover_cursor(egui::CursorIcon::PointingHand)
                             .clicked()
                         {
+                            *ui_state = UIState::LevelSelect;
+                        }
+                        ui.add_space(20.);
+                        if ui
+                            .add_sized(
+                                [220.0, 80.0],
+                                egui::Button::new("Endless"),
+                            )
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            *game_mode = GameMode::Endless;
                             app_state
-                                .set(SceneState::InGame)
+                                .set(SceneState::LoadingLevel)
                                 .unwrap_or_default();
                         }
+                        ui.add_space(20.);
+                        if ui
+                            .add_sized(
+                                [220.0, 80.0],
+                                egui::Button::new(format!(
+                                    "Difficulty: {:?}",
+                                    difficulty.preset
+                                )),
+                            )
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            *ui_state = UIState::Difficulty;
+                        }
                         ui.add_space(50.);
                         if ui
                             .add_sized(
@@ -402,6 +731,22 @@ fn main_menu_logic(
                         {
                             *ui_state = UIState::Options;
                         }
+                        #[cfg(feature = "debug_gui")]
+                        {
+                            ui.add_space(20.);
+                            if ui
+                                .add_sized(
+                                    [220.0, 80.0],
+                                    egui::Button::new("Level editor"),
+                                )
+                                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .clicked()
+                            {
+                                app_state
+                                    .set(SceneState::Editor)
+                                    .unwrap_or_default();
+                            }
+                        }
 
                         ui.separator();
                         ui.add_space(20.);
@@ -420,6 +765,98 @@ fn main_menu_logic(
         });
 }
 
+fn level_select_menu_system(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut ui_state: ResMut<UIState>,
+    mut app_state: ResMut<State<SceneState>>,
+    mut campaign: ResMut<Campaign>,
+    progress: Res<CampaignProgress>,
+    mut level_manager: ResMut<LevelManager>,
+    levels: Res<Assets<LevelAsset>>,
+    mut game_mode: ResMut<GameMode>,
+) {
+    if *ui_state != UIState::LevelSelect {
+        return;
+    }
+    egui::Window::new("")
+        .id(egui::Id::new(3))
+        .resizable(false)
+        .title_bar(false)
+        .frame(egui::Frame {
+            fill: egui::Color32::from_rgb(115, 99, 114),
+            shadow: egui::epaint::Shadow::small_light(),
+            rounding: egui::Rounding::from(8.),
+            ..Default::default()
+        })
+        .default_pos(egui::Pos2 { x: -500., y: -500. })
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2 { x: 0., y: 300. })
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let widget_visuals = egui::style::WidgetVisuals {
+                bg_fill: egui::Color32::from_rgb(170, 192, 170),
+                bg_stroke: egui::Stroke {
+                    width: 1.,
+                    color: egui::Color32::from_rgb(220, 238, 209),
+                },
+                rounding: egui::Rounding::from(8.),
+                fg_stroke: egui::Stroke {
+                    width: 5.,
+                    color: egui::Color32::BLACK,
+                },
+                expansion: 0.,
+            };
+            ui.visuals_mut().widgets = egui::style::Widgets {
+                inactive: widget_visuals,
+                ..Default::default()
+            };
+
+            ui.vertical_centered(|ui| {
+                ui.add_space(20.);
+                ui.label("Select level");
+                ui.add_space(20.);
+                for index in 0..campaign.levels.len() {
+                    let name = levels
+                        .get(&campaign.levels[index])
+                        .map(|level| level.name.clone())
+                        .unwrap_or_else(|| "Loading...".to_string());
+                    let unlocked = progress.is_unlocked(index);
+                    let label = if unlocked {
+                        format!("{}. {}", index + 1, name)
+                    } else {
+                        format!("{}. Locked", index + 1)
+                    };
+                    if ui
+                        .add_enabled_ui(unlocked, |ui| {
+                            ui.add_sized(
+                                [220.0, 60.0],
+                                egui::Button::new(label),
+                            )
+                        })
+                        .inner
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        select_level(index, &mut campaign, &mut level_manager);
+                        *game_mode = GameMode::Campaign;
+                        *ui_state = UIState::None;
+                        app_state
+                            .set(SceneState::LoadingLevel)
+                            .unwrap_or_default();
+                    }
+                    ui.add_space(10.);
+                }
+                ui.separator();
+                if ui
+                    .add_sized([220.0, 80.0], egui::Button::new("Back to Menu"))
+                    .on_hover_cursor(egui::CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    *ui_state = UIState::None;
+                }
+                ui.add_space(20.);
+            });
+        });
+}
+
 #[derive(Component)]
 pub struct MainMenuNode;
 fn setup_main_menu(
@@ -594,6 +1031,7 @@ fn ui_menus_system(
     mut egui_ctx: ResMut<EguiContext>,
     mut ui_state: ResMut<UIState>,
     mut options: ResMut<Options>,
+    mut difficulty: ResMut<Difficulty>,
 ) {
     match *ui_state {
         UIState::Options => {
@@ -664,6 +1102,112 @@ fn ui_menus_system(
                     });
                 });
         }
+        UIState::Difficulty => {
+            egui::Window::new("")
+                .id(egui::Id::new(4))
+                .resizable(false)
+                .title_bar(false)
+                .frame(egui::Frame {
+                    fill: egui::Color32::from_rgb(115, 99, 114),
+                    shadow: egui::epaint::Shadow::small_light(),
+                    rounding: egui::Rounding::from(8.),
+                    ..Default::default()
+                })
+                .default_pos(egui::Pos2 { x: -500., y: -500. })
+                .anchor(egui::Align2::CENTER_TOP, egui::Vec2 { x: 0., y: 200. })
+                .show(egui_ctx.ctx_mut(), |ui| {
+                    let widget_visuals = egui::style::WidgetVisuals {
+                        bg_fill: egui::Color32::from_rgb(170, 192, 170),
+                        bg_stroke: egui::Stroke {
+                            width: 1.,
+                            color: egui::Color32::from_rgb(220, 238, 209),
+                        },
+                        rounding: egui::Rounding::from(8.),
+                        fg_stroke: egui::Stroke {
+                            width: 5.,
+                            color: egui::Color32::BLACK,
+                        },
+                        expansion: 0.,
+                    };
+                    ui.visuals_mut().widgets = egui::style::Widgets {
+                        inactive: widget_visuals,
+                        ..Default::default()
+                    };
+
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(20.);
+                        ui.label("Difficulty");
+                        ui.add_space(20.);
+                        ui.horizontal(|ui| {
+                            for preset in DifficultyPreset::ALL {
+                                if ui
+                                    .selectable_label(
+                                        difficulty.preset == preset,
+                                        format!("{:?}", preset),
+                                    )
+                                    .clicked()
+                                {
+                                    // start the custom preset from the one
+                                    // that was selected before
+                                    if preset == DifficultyPreset::Custom {
+                                        difficulty.custom =
+                                            difficulty.selected();
+                                    }
+                                    difficulty.preset = preset;
+                                }
+                            }
+                        });
+                        ui.add_space(20.);
+                        let custom =
+                            difficulty.preset == DifficultyPreset::Custom;
+                        let mut settings = difficulty.selected();
+                        ui.add_enabled_ui(custom, |ui| {
+                            for (label, value) in [
+                                ("Enemy health", &mut settings.enemy.health),
+                                ("Enemy damage", &mut settings.enemy.damage),
+                                (
+                                    "Enemy attack speed",
+                                    &mut settings.enemy.attack_speed,
+                                ),
+                                (
+                                    "Time between waves",
+                                    &mut settings.wave_delay,
+                                ),
+                                (
+                                    "Starting bloodrock",
+                                    &mut settings.starting_bloodrock,
+                                ),
+                                ("Unit cost", &mut settings.unit_cost),
+                                ("Supply cap", &mut settings.supply_cap),
+                            ] {
+                                ui.label(label);
+                                ui.add_sized(
+                                    [200.0, 30.0],
+                                    egui::Slider::new(value, MULTIPLIER_RANGE)
+                                        .suffix("x"),
+                                );
+                            }
+                        });
+                        if custom {
+                            difficulty.custom = settings;
+                        }
+                        ui.add_space(20.);
+                        ui.separator();
+                        if ui
+                            .add_sized(
+                                [220.0, 80.0],
+                                egui::Button::new("Back to Menu"),
+                            )
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            difficulty.save();
+                            *ui_state = UIState::None
+                        }
+                        ui.add_space(20.);
+                    });
+                });
+        }
         _ => {}
     }
 }
@@ -672,6 +1216,8 @@ fn pause_menu_logic(
     mut egui_ctx: ResMut<EguiContext>,
     mut ui_state: ResMut<UIState>,
     mut app_state: ResMut<State<SceneState>>,
+    rng: Res<GameRng>,
+    mut save_game: EventWriter<SaveGameEvent>,
 ) {
     egui::Window::new("")
         .id(egui::Id::new(1))
@@ -719,6 +1265,14 @@ fn pause_menu_logic(
                             app_state.pop().unwrap_or_default();
                         }
                         ui.add_space(20.);
+                        if ui
+                            .add_sized([220.0, 80.0], egui::Button::new("Save"))
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            save_game.send(SaveGameEvent);
+                        }
+                        ui.add_space(20.);
                         if ui
                             .add_sized(
                                 [220.0, 80.0],
@@ -745,6 +1299,8 @@ fn pause_menu_logic(
                                 .unwrap_or_default();
                         }
                         ui.add_space(20.);
+                        ui.label(format!("Seed: {}", rng.seed()));
+                        ui.add_space(10.);
                     });
                 }
                 _ => {}
@@ -785,6 +1341,18 @@ pub enum EndGameState {
 pub struct EndGameManager {
     pub state: EndGameState,
     pub time_to_fade_in: Timer,
+    /// How the level objectives ended up, shown on the end screen
+    pub objectives: Vec<String>,
+}
+
+impl Default for EndGameManager {
+    fn default() -> Self {
+        EndGameManager {
+            time_to_fade_in: Timer::from_seconds(1., false),
+            state: EndGameState::NotEndGame,
+            objectives: Vec::new(),
+        }
+    }
 }
 
 use std::time::Duration;
@@ -803,6 +1371,12 @@ fn end_game_manager_system(
     mut cmd: Commands,
     fader_screen: Query<Entity, With<FaderScreenComponent>>,
     mut tip_index: Local<usize>,
+    mut rng: ResMut<GameRng>,
+    mut campaign: ResMut<Campaign>,
+    mut progress: ResMut<CampaignProgress>,
+    mut level_manager: ResMut<LevelManager>,
+    game_mode: Res<GameMode>,
+    endless_run: Res<EndlessRun>,
 ) {
     match end_game_manager.state {
         EndGameState::Lose => {
@@ -813,12 +1387,13 @@ fn end_game_manager_system(
                 "Combine a marksman and a worker to get a healer!",
                 "Combine a swordsman and a worker to get a tank!",
                 "Having a good mixture of units is key!",
+                "Press U on a merged unit to split it back up!",
+                "Drag a box around your units and right click to order them!",
             ];
             if end_game_manager.time_to_fade_in.elapsed()
                 == Duration::from_millis(0)
             {
-                let mut rng = rand::thread_rng();
-                *tip_index = rng.gen_range(0..tips.len());
+                *tip_index = rng.cosmetic.gen_range(0..tips.len());
                 for e in fader_screen.iter() {
                     cmd.entity(e).insert(Fade {
                         start_color: Color::rgba(0., 0., 0., 0.),
@@ -831,7 +1406,13 @@ fn end_game_manager_system(
             end_game_manager.time_to_fade_in.tick(time.delta());
             if end_game_manager.time_to_fade_in.finished() {
                 for mut text in end_game_text.iter_mut() {
-                    text.sections[0].value = format!("You lost!");
+                    text.sections[0].value = match *game_mode {
+                        GameMode::Campaign => "You lost!".to_string(),
+                        GameMode::Endless => format!(
+                            "You survived {}!",
+                            format_survival_time(endless_run.elapsed)
+                        ),
+                    };
                 }
                 for mut text in tip_text.iter_mut() {
                     text.sections[0].value =
@@ -843,6 +1424,7 @@ fn end_game_manager_system(
             if end_game_manager.time_to_fade_in.elapsed()
                 == Duration::from_millis(0)
             {
+                progress.complete_level(campaign.current);
                 for e in fader_screen.iter() {
                     cmd.entity(e).insert(Fade {
                         start_color: Color::rgba(0., 0., 0., 0.),
@@ -860,8 +1442,11 @@ fn end_game_manager_system(
                 }
 
                 for mut text in tip_text.iter_mut() {
-                    text.sections[0].value =
-                        format!("Thanks for playing our game!");
+                    text.sections[0].value = if campaign.has_next_level() {
+                        "The next level is unlocked!".to_string()
+                    } else {
+                        "Thanks for playing our game!".to_string()
+                    };
                 }
             }
         }
@@ -904,6 +1489,43 @@ fn end_game_manager_system(
                 };
 
                 ui.vertical_centered(|ui| {
+                    if !end_game_manager.objectives.is_empty() {
+                        ui.add_space(10.);
+                        for objective in end_game_manager.objectives.iter() {
+                            ui.label(
+                                egui::RichText::new(objective)
+                                    .size(18.)
+                                    .color(egui::Color32::WHITE),
+                            );
+                        }
+                        ui.add_space(10.);
+                        ui.separator();
+                    }
+                    if matches!(end_game_manager.state, EndGameState::Win)
+                        && *game_mode == GameMode::Campaign
+                        && campaign.has_next_level()
+                    {
+                        ui.add_space(20.);
+                        if ui
+                            .add_sized(
+                                [220.0, 80.0],
+                                egui::Button::new("Next level"),
+                            )
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            let next = campaign.current + 1;
+                            select_level(
+                                next,
+                                &mut campaign,
+                                &mut level_manager,
+                            );
+                            app_state
+                                .set(SceneState::LoadingLevel)
+                                .unwrap_or_default();
+                        }
+                        ui.add_space(20.);
+                    }
                     ui.separator();
 
                     if ui
@@ -925,10 +1547,7 @@ fn end_game_manager_system(
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(UIState::None)
-            .insert_resource(EndGameManager {
-                time_to_fade_in: Timer::from_seconds(1., false),
-                state: EndGameState::NotEndGame,
-            })
+            .insert_resource(EndGameManager::default())
             .add_startup_system(ui_first_setup)
             .add_system(fader_system)
             .add_system(ui_menus_system)
@@ -940,6 +1559,10 @@ impl Plugin for UIPlugin {
                 SystemSet::on_update(SceneState::InGame)
                     .with_system(update_bloodrock_text)
                     .with_system(update_supply_text)
+                    .with_system(update_objectives_text)
+                    .with_system(update_boss_bar_system)
+                    .with_system(update_loot_buffs_text)
+                    .with_system(update_formation_text)
                     .with_system(end_game_manager_system),
             )
             .add_system_set(
@@ -947,9 +1570,14 @@ impl Plugin for UIPlugin {
                     .with_system(setup_main_menu)
                     .with_system(destroy_in_game_ui),
             )
+            .add_system_set(
+                SystemSet::on_enter(SceneState::LoadingLevel)
+                    .with_system(destroy_in_game_ui),
+            )
             .add_system_set(
                 SystemSet::on_update(SceneState::MainMenu)
-                    .with_system(main_menu_logic),
+                    .with_system(main_menu_logic)
+                    .with_system(level_select_menu_system),
             )
             .add_system_set(
                 SystemSet::on_exit(SceneState::MainMenu)
@@ -0,0 +1,302 @@
+use anyhow::{anyhow, bail};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enemy_archetype::{CombatDescriptor, HealerDescriptor, TankDescriptor},
+    worker_logic::{UnitClass, UnitSize},
+};
+
+const STATS_FILE: &str = "units.stats.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HarvesterDescriptor {
+    /// Seconds per bloodrock
+    pub harvest_time: f32,
+    /// Bloodrock it can carry back to the player at once
+    pub capacity: usize,
+}
+
+/// Stats of one class at one size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitStats {
+    pub health: f32,
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default)]
+    pub combat: Option<CombatDescriptor>,
+    #[serde(default)]
+    pub harvester: Option<HarvesterDescriptor>,
+    #[serde(default)]
+    pub tank: Option<TankDescriptor>,
+    #[serde(default)]
+    pub healer: Option<HealerDescriptor>,
+}
+
+impl UnitStats {
+    /// Which of combat, harvester, tank and healer it has
+    pub(crate) fn roles(&self) -> [bool; 4] {
+        [
+            self.combat.is_some(),
+            self.harvester.is_some(),
+            self.tank.is_some(),
+            self.healer.is_some(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizedStats {
+    #[serde(rename = "Small")]
+    pub small: UnitStats,
+    #[serde(rename = "Medium")]
+    pub medium: UnitStats,
+    #[serde(rename = "Huge")]
+    pub huge: UnitStats,
+}
+
+impl SizedStats {
+    pub fn get(&self, size: UnitSize) -> &UnitStats {
+        match size {
+            UnitSize::Small => &self.small,
+            UnitSize::Medium => &self.medium,
+            UnitSize::Huge => &self.huge,
+        }
+    }
+}
+
+/// Added to the stats for every point of scale a unit grew by eating
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrowthDescriptor {
+    pub damage: f32,
+    /// Seconds
+    pub time_between_attacks: f32,
+    /// Seconds
+    pub harvest_time: f32,
+    pub capacity: f32,
+}
+
+/// Unit stats as they are stored in `assets/units.stats.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "c4f2a7d9-1e6b-4f83-a05c-8d3e9b7f2a16"]
+pub struct UnitStatsAsset {
+    #[serde(default)]
+    pub growth: GrowthDescriptor,
+    pub classes: HashMap<UnitClass, SizedStats>,
+}
+
+pub fn parse_unit_stats(bytes: &[u8]) -> Result<UnitStatsAsset, anyhow::Error> {
+    let asset: UnitStatsAsset = serde_json::from_slice(bytes)
+        .map_err(|err| anyhow!("malformed unit stats file: {}", err))?;
+    for class in UnitClass::ALL {
+        let sized = asset.classes.get(&class).ok_or_else(|| {
+            anyhow!("{}: missing from the stats", class.name())
+        })?;
+        // units keep their components when they grow
+        if sized.medium.roles() != sized.small.roles()
+            || sized.huge.roles() != sized.small.roles()
+        {
+            bail!("{}: every size needs the same roles", class.name());
+        }
+        for stats in [&sized.small, &sized.medium, &sized.huge] {
+            validate_stats(class, stats)?;
+        }
+    }
+    Ok(asset)
+}
+
+fn validate_stats(
+    class: UnitClass,
+    stats: &UnitStats,
+) -> Result<(), anyhow::Error> {
+    if stats.health <= 0. || !(0.0..1.0).contains(&stats.armor) {
+        bail!("{}: needs positive health and armor below 1", class.name());
+    }
+    let mut positive = Vec::new();
+    if let Some(combat) = &stats.combat {
+        positive.extend([combat.time_between_attacks, combat.attack_range]);
+    }
+    if let Some(harvester) = &stats.harvester {
+        if harvester.capacity == 0 {
+            bail!("{}: harvesters need to carry something", class.name());
+        }
+        positive.push(harvester.harvest_time);
+    }
+    if let Some(tank) = &stats.tank {
+        positive.push(tank.time_between_taunts);
+    }
+    if let Some(healer) = &stats.healer {
+        positive.extend([
+            healer.heal_amount,
+            healer.range,
+            healer.time_between_heals,
+        ]);
+    }
+    if positive.iter().any(|value| *value <= 0.) {
+        bail!(
+            "{}: timings, ranges and heals have to be positive",
+            class.name()
+        );
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct UnitStatsLoader;
+
+impl AssetLoader for UnitStatsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let stats = parse_unit_stats(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(stats));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stats.json"]
+    }
+}
+
+/// Unit stats of the loaded stats file
+#[derive(Default)]
+pub struct UnitStatsTable {
+    pub handle: Handle<UnitStatsAsset>,
+    pub growth: GrowthDescriptor,
+    pub classes: HashMap<UnitClass, SizedStats>,
+}
+
+impl UnitStatsTable {
+    pub fn is_loaded(&self) -> bool {
+        !self.classes.is_empty()
+    }
+
+    pub fn get(&self, class: UnitClass, size: UnitSize) -> Option<&UnitStats> {
+        self.classes.get(&class).map(|sized| sized.get(size))
+    }
+}
+
+fn load_unit_stats_system(
+    asset_server: Res<AssetServer>,
+    mut stats: ResMut<UnitStatsTable>,
+) {
+    stats.handle = asset_server.load(STATS_FILE);
+}
+
+/// Picks up the stats once they finished loading, or got hot reloaded
+pub(crate) fn unit_stats_asset_event_system(
+    mut events: EventReader<AssetEvent<UnitStatsAsset>>,
+    assets: Res<Assets<UnitStatsAsset>>,
+    mut stats: ResMut<UnitStatsTable>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle }
+            | AssetEvent::Modified { handle } => {
+                if *handle != stats.handle {
+                    continue;
+                }
+                let asset = match assets.get(handle) {
+                    Some(asset) => asset,
+                    None => continue,
+                };
+                info!("loaded stats of {} unit classes", asset.classes.len());
+                stats.growth = asset.growth.clone();
+                stats.classes = asset.classes.clone();
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+pub struct UnitStatsPlugin;
+
+impl Plugin for UnitStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<UnitStatsAsset>()
+            .init_asset_loader::<UnitStatsLoader>()
+            .init_resource::<UnitStatsTable>()
+            .add_startup_system(load_unit_stats_system)
+            .add_system(unit_stats_asset_event_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIPPED: &[u8] = include_bytes!("../assets/units.stats.json");
+
+    /// The shipped stats with `edit` applied to them
+    fn edited(edit: impl FnOnce(&mut serde_json::Value)) -> Vec<u8> {
+        let mut json: serde_json::Value =
+            serde_json::from_slice(SHIPPED).unwrap();
+        edit(&mut json);
+        serde_json::to_vec(&json).unwrap()
+    }
+
+    #[test]
+    fn table_has_every_class_and_size() {
+        let asset = parse_unit_stats(SHIPPED).unwrap();
+        let table = UnitStatsTable {
+            growth: asset.growth,
+            classes: asset.classes,
+            ..Default::default()
+        };
+        assert!(table.is_loaded());
+        for class in UnitClass::ALL {
+            for size in [UnitSize::Small, UnitSize::Medium, UnitSize::Huge] {
+                assert!(table.get(class, size).is_some(), "{:?}", class);
+            }
+        }
+        let worker = table.get(UnitClass::Worker, UnitSize::Huge).unwrap();
+        assert_eq!(worker.harvester.as_ref().map(|h| h.capacity), Some(5));
+        assert!(UnitStatsTable::default()
+            .get(UnitClass::Worker, UnitSize::Small)
+            .is_none());
+    }
+
+    #[test]
+    fn missing_classes_are_rejected() {
+        let bytes = edited(|json| {
+            json["classes"].as_object_mut().unwrap().remove("Healer");
+        });
+        let err = parse_unit_stats(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "Healer: missing from the stats");
+    }
+
+    #[test]
+    fn sizes_need_the_same_roles() {
+        let bytes = edited(|json| {
+            json["classes"]["Worker"]["Huge"]
+                .as_object_mut()
+                .unwrap()
+                .remove("harvester");
+        });
+        let err = parse_unit_stats(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "Worker: every size needs the same roles");
+    }
+
+    #[test]
+    fn harvesters_need_capacity() {
+        let bytes = edited(|json| {
+            json["classes"]["Worker"]["Small"]["harvester"]["capacity"] =
+                0.into();
+        });
+        let err = parse_unit_stats(&bytes).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Worker: harvesters need to carry something"
+        );
+    }
+}
@@ -1,12 +1,20 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::Duration};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    combat::{AttackState, AttackType, CombatComponent},
-    enemy_logic::BasicEnemyLogic,
-    game::{GameAssets, Harvester, PlayerController, UnitType},
+    combat::{AttackState, CombatComponent},
+    game::{GameAssets, Harvester, PlayerController, UnitType, Velocity},
     get_children_recursive,
     health::Health,
     interaction::{MouseFollow, Selected},
+    merge_recipes::{ClassId, MergeRecipes, TierClass},
+    orders::UnitOrder,
+    replay::PlayerInput,
+    targeting::Targeting,
+    unit_stats::{
+        unit_stats_asset_event_system, UnitStats, UnitStatsAsset,
+        UnitStatsTable,
+    },
     GameTime, SceneState,
 };
 
@@ -36,7 +44,9 @@ pub struct HealerComponent {
     pub target_type: UnitType,
 }
 
-#[derive(Component, Clone, Copy, PartialEq)]
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub enum UnitClass {
     Worker,
     Ranged,
@@ -45,79 +55,191 @@ pub enum UnitClass {
     Piker,
     Healer,
 }
-#[derive(Component, Clone, Copy, PartialEq)]
-pub enum UnitSize {
-    Small,
-    Medium,
-    Huge,
-}
 
-#[derive(Component)]
-pub struct CanEatWorker {
-    pub entity_to_eat: Option<Entity>,
-}
+impl UnitClass {
+    pub const ALL: [UnitClass; 6] = [
+        UnitClass::Worker,
+        UnitClass::Ranged,
+        UnitClass::Sworder,
+        UnitClass::Tank,
+        UnitClass::Piker,
+        UnitClass::Healer,
+    ];
 
-fn get_next_size(size: UnitSize) -> UnitSize {
-    match size {
-        UnitSize::Small => {
-            return UnitSize::Medium;
-        }
-        UnitSize::Medium => {
-            return UnitSize::Huge;
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitClass::Worker => "Worker",
+            UnitClass::Ranged => "Ranged",
+            UnitClass::Sworder => "Sworder",
+            UnitClass::Tank => "Tank",
+            UnitClass::Piker => "Piker",
+            UnitClass::Healer => "Healer",
         }
-        UnitSize::Huge => {
-            return UnitSize::Huge;
+    }
+
+    pub fn from_name(name: &str) -> Option<UnitClass> {
+        UnitClass::ALL
+            .into_iter()
+            .find(|class| class.name() == name)
+    }
+
+    /// Melee in front, ranged and healers behind, workers off to the sides
+    pub fn formation_role(&self) -> FormationRole {
+        match self {
+            UnitClass::Sworder | UnitClass::Piker | UnitClass::Tank => {
+                FormationRole::Front
+            }
+            UnitClass::Ranged | UnitClass::Healer => FormationRole::Back,
+            UnitClass::Worker => FormationRole::Side,
         }
     }
 }
 
-pub fn merge_units(
-    unit_alpha: (UnitClass, UnitSize),
-    unit_beta: (UnitClass, UnitSize),
-) -> (UnitClass, UnitSize) {
-    let unit_alpha_size = unit_alpha.1;
-    let unit_beta_size = unit_beta.1;
-    let unit_alpha_class = unit_alpha.0;
-    let unit_beta_class = unit_beta.0;
-    let mut return_var = unit_alpha.clone();
-
-    if unit_alpha_class == unit_beta_class && unit_alpha_size == unit_beta_size
-    {
-        return_var.1 = get_next_size(return_var.1);
+/// Distance between two slots of a formation
+const SLOT_SPACING: f32 = 90.;
+/// Distance of the closest slots to the player
+const FORMATION_DEPTH: f32 = 150.;
+/// How far to the sides of the player the workers walk
+const FORMATION_SIDE: f32 = 220.;
+/// Slots per ring of the ring formation, the rest go on rings further out
+const RING_SIZE: usize = 4;
+/// Followers this close to their slot stay put
+const SLOT_TOLERANCE: f32 = 15.;
+const MAX_FOLLOW_SPEED: f32 = 350.;
+/// How fast the formation turns towards where the player walks
+const FORMATION_TURN_SPEED: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormationKind {
+    /// Rows of four in front of and behind the player
+    Block,
+    /// One wide row in front and one behind
+    Line,
+    /// Arcs around the player
+    Ring,
+}
+
+impl FormationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FormationKind::Block => "Block",
+            FormationKind::Line => "Line",
+            FormationKind::Ring => "Ring",
+        }
     }
 
-    match unit_alpha_class {
-        UnitClass::Worker => match unit_beta_class {
-            UnitClass::Ranged => {
-                return_var.0 = UnitClass::Healer;
+    fn next(self) -> Self {
+        match self {
+            FormationKind::Block => FormationKind::Line,
+            FormationKind::Line => FormationKind::Ring,
+            FormationKind::Ring => FormationKind::Block,
+        }
+    }
+
+    /// Offset of slot `index` of the `count` slots of `role` from the player,
+    /// x points where the formation faces and y to its left
+    fn slot(self, role: FormationRole, index: usize, count: usize) -> Vec2 {
+        match (self, role) {
+            (FormationKind::Ring, FormationRole::Front) => {
+                ring_slot(0., index, count)
             }
-            UnitClass::Sworder => {
-                return_var.0 = UnitClass::Tank;
+            (FormationKind::Ring, FormationRole::Back) => {
+                ring_slot(std::f32::consts::PI, index, count)
             }
-            _ => {}
-        },
-        UnitClass::Sworder => match unit_beta_class {
-            UnitClass::Worker => {
-                return_var.0 = UnitClass::Tank;
+            // alternating between the left and the right
+            (FormationKind::Ring, FormationRole::Side) => {
+                let (center, side_count) = if index % 2 == 0 {
+                    (std::f32::consts::FRAC_PI_2, (count + 1) / 2)
+                } else {
+                    (-std::f32::consts::FRAC_PI_2, count / 2)
+                };
+                ring_slot(center, index / 2, side_count)
             }
-            UnitClass::Ranged => {
-                return_var.0 = UnitClass::Piker;
+            (_, FormationRole::Side) => {
+                let side = if index % 2 == 0 { 1. } else { -1. };
+                Vec2::new(
+                    -((index / 2) as f32) * SLOT_SPACING,
+                    side * FORMATION_SIDE,
+                )
             }
-            _ => {}
-        },
-        UnitClass::Ranged => match unit_beta_class {
-            UnitClass::Sworder => {
-                return_var.0 = UnitClass::Piker;
+            (FormationKind::Block, FormationRole::Front) => {
+                row_slot(1., index, count, 4)
             }
-            UnitClass::Worker => {
-                return_var.0 = UnitClass::Healer;
+            (FormationKind::Block, FormationRole::Back) => {
+                row_slot(-1., index, count, 4)
             }
-            _ => {}
-        },
+            (FormationKind::Line, FormationRole::Front) => {
+                row_slot(1., index, count, 8)
+            }
+            (FormationKind::Line, FormationRole::Back) => {
+                row_slot(-1., index, count, 8)
+            }
+        }
+    }
+}
+
+/// Rows in front of the player for `direction` 1, behind it for -1
+fn row_slot(
+    direction: f32,
+    index: usize,
+    count: usize,
+    per_row: usize,
+) -> Vec2 {
+    let row = index / per_row;
+    let in_row = (count - row * per_row).min(per_row);
+    let across = (index % per_row) as f32 - (in_row - 1) as f32 / 2.;
+    Vec2::new(
+        direction * (FORMATION_DEPTH + row as f32 * SLOT_SPACING),
+        across * SLOT_SPACING,
+    )
+}
 
-        _ => {}
+/// Arcs centered on the `center` angle
+fn ring_slot(center: f32, index: usize, count: usize) -> Vec2 {
+    let ring = index / RING_SIZE;
+    let in_ring = (count - ring * RING_SIZE).min(RING_SIZE);
+    let radius = FORMATION_DEPTH + ring as f32 * SLOT_SPACING;
+    let across = (index % RING_SIZE) as f32 - (in_ring - 1) as f32 / 2.;
+    let angle = center + across * SLOT_SPACING / radius;
+    Vec2::new(angle.cos(), angle.sin()) * radius
+}
+
+/// Where a class stands in the formation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormationRole {
+    Front,
+    Back,
+    Side,
+}
+
+/// How the allies line up around the player
+pub struct Formation {
+    pub kind: FormationKind,
+    /// Normalized direction the formation faces
+    pub facing: Vec2,
+}
+
+impl Default for Formation {
+    fn default() -> Self {
+        Formation {
+            kind: FormationKind::Block,
+            facing: Vec2::X,
+        }
     }
-    return return_var;
+}
+
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum UnitSize {
+    Small,
+    Medium,
+    Huge,
+}
+
+#[derive(Component)]
+pub struct CanEatWorker {
+    pub entity_to_eat: Option<Entity>,
 }
 
 fn get_index_from_unit_class(class: UnitClass) -> usize {
@@ -132,10 +254,15 @@ fn get_index_from_unit_class(class: UnitClass) -> usize {
 }
 
 fn change_sprite_based_on_class_system(
-    mut workers: Query<(&UnitClass, &UnitSize, &mut TextureAtlasSprite)>,
+    mut workers: Query<(
+        &UnitClass,
+        &UnitSize,
+        Option<&TierClass>,
+        &mut TextureAtlasSprite,
+    )>,
 ) {
     let mut sprite_index;
-    for (class, size, mut sprite) in workers.iter_mut() {
+    for (class, size, tier_class, mut sprite) in workers.iter_mut() {
         sprite_index = get_index_from_unit_class(*class);
         match size {
             UnitSize::Small => {
@@ -149,199 +276,202 @@ fn change_sprite_based_on_class_system(
             }
         }
         sprite.index = sprite_index;
+        sprite.color = tier_class
+            .and_then(|tier_class| tier_class.desc.color)
+            .map_or(Color::WHITE, |[r, g, b]| Color::rgb(r, g, b));
     }
 }
 
+/// Keeps units in line with the stats table, so edits of the stats file show
+/// up right away
 fn set_stats_based_on_class_and_size_system(
-    units: Query<(Entity, &UnitClass, &UnitSize, &Transform)>,
+    stats_table: Res<UnitStatsTable>,
+    mut units: Query<(
+        Entity,
+        &UnitClass,
+        &UnitSize,
+        Option<&TierClass>,
+        &Transform,
+        &mut Health,
+    )>,
     mut combat_comps: Query<&mut CombatComponent>,
     mut harvester_comps: Query<&mut Harvester>,
-    //mut healer_comps: Query<&mut HealerComponent>,
-    //mut tank_comps: Query<&TankComponent>,
+    mut healer_comps: Query<&mut HealerComponent>,
 ) {
-    for (e, class, size, tr) in units.iter() {
-        if let Ok(mut combat_comp) = combat_comps.get_mut(e) {
-            let mut damage = 1.;
-            let mut time_to_attack = 1.;
-            match class {
-                UnitClass::Piker => {
-                    damage = 1.3;
-                    time_to_attack = 1.2;
-                }
-                UnitClass::Sworder => {
-                    damage = 1.2;
-                    time_to_attack = 0.75;
-                }
-                UnitClass::Ranged => {
-                    damage = 0.5;
-                    time_to_attack = 1.;
-                }
-                _ => {}
-            }
-            match size {
-                UnitSize::Small => {}
-                UnitSize::Medium => {
-                    damage *= 2.;
-                    time_to_attack /= 1.5;
-                }
-                UnitSize::Huge => {
-                    damage *= 3.;
-                    time_to_attack /= 3.;
-                }
+    let growth = &stats_table.growth;
+    for (e, class, size, tier_class, tr, mut health) in units.iter_mut() {
+        let stats = match stats_table.get(*class, *size) {
+            Some(stats) => stats,
+            None => continue,
+        };
+        // units grow by eating each other
+        let grown = tr.scale.x - 1.;
+        let (damage_multiplier, health_multiplier) = tier_class
+            .map_or((1., 1.), |tier_class| {
+                (tier_class.desc.damage, tier_class.desc.health)
+            });
+
+        let max_health = stats.health * health_multiplier;
+        if health.max_health != max_health {
+            health.current_health *= max_health / health.max_health;
+            health.max_health = max_health;
+        }
+        if health.armor != stats.armor {
+            health.armor = stats.armor;
+        }
+        if let (Ok(mut combat_comp), Some(combat)) =
+            (combat_comps.get_mut(e), &stats.combat)
+        {
+            combat_comp.damage =
+                combat.damage * damage_multiplier + growth.damage * grown;
+            combat_comp.attack_range = combat.attack_range;
+            combat_comp.piercing = combat.piercing;
+            let time_to_attack = Duration::from_secs_f32(
+                (combat.time_between_attacks
+                    + growth.time_between_attacks * grown)
+                    .max(0.05),
+            );
+            if combat_comp.time_between_attacks.duration() != time_to_attack {
+                combat_comp
+                    .time_between_attacks
+                    .set_duration(time_to_attack);
             }
-            damage += 1. - tr.scale.x;
-            time_to_attack -= (1. - tr.scale.x) / 10.;
-            combat_comp.damage = damage;
-            if combat_comp.time_between_attacks.duration()
-                != bevy::utils::Duration::from_secs_f32(time_to_attack)
-            {
-                combat_comp.time_between_attacks.set_duration(
-                    bevy::utils::Duration::from_secs_f32(time_to_attack),
-                );
+        }
+        if let (Ok(mut harvester_comp), Some(harvester)) =
+            (harvester_comps.get_mut(e), &stats.harvester)
+        {
+            harvester_comp.max_carryable_resource =
+                (harvester.capacity as f32 + growth.capacity * grown).max(1.)
+                    as usize;
+            let harvest_speed = Duration::from_secs_f32(
+                (harvester.harvest_time + growth.harvest_time * grown)
+                    .max(0.05),
+            );
+            if harvester_comp.harvest_speed.duration() != harvest_speed {
+                harvester_comp.harvest_speed.set_duration(harvest_speed);
             }
         }
-        if let Ok(mut harvester_comp) = harvester_comps.get_mut(e) {
-            let mut harvest_speed = 1.;
-            let mut max_carryable_resource: usize = 3;
-            match size {
-                UnitSize::Small => {}
-                UnitSize::Medium => {
-                    harvest_speed = 0.80;
-                    max_carryable_resource = 4;
-                }
-                UnitSize::Huge => {
-                    harvest_speed = 0.5;
-                    max_carryable_resource = 5;
-                }
+        if let (Ok(mut healer_comp), Some(healer)) =
+            (healer_comps.get_mut(e), &stats.healer)
+        {
+            healer_comp.heal_amount = healer.heal_amount;
+            healer_comp.range = healer.range;
+            let time_between_heals =
+                Duration::from_secs_f32(healer.time_between_heals);
+            if healer_comp.time_between_heals.duration() != time_between_heals {
+                healer_comp
+                    .time_between_heals
+                    .set_duration(time_between_heals);
             }
-            max_carryable_resource += ((1. - tr.scale.x) * 5.) as usize;
-            harvest_speed -= (1. - tr.scale.x) / 10.;
-
-            harvester_comp.max_carryable_resource = max_carryable_resource;
+        }
+    }
+}
 
-            if harvester_comp.harvest_speed.duration()
-                != bevy::utils::Duration::from_secs_f32(harvest_speed)
-            {
-                harvester_comp.harvest_speed.set_duration(
-                    bevy::utils::Duration::from_secs_f32(harvest_speed),
-                );
+/// Gives units the components of their class whenever it changes, or when a
+/// hot reload of the stats file changes the roles of their class. The stats
+/// are kept up to date by `set_stats_based_on_class_and_size_system`
+fn apply_class_system(
+    mut cmd: Commands,
+    mut stats_events: EventReader<AssetEvent<UnitStatsAsset>>,
+    stats_table: Res<UnitStatsTable>,
+    units: Query<(
+        Entity,
+        &UnitClass,
+        &UnitSize,
+        ChangeTrackers<UnitClass>,
+        Option<&CombatComponent>,
+        Option<&Harvester>,
+        Option<&TankComponent>,
+        Option<&HealerComponent>,
+    )>,
+) {
+    let reloaded = stats_events.iter().any(|event| {
+        matches!(
+            event,
+            AssetEvent::Modified { handle } if *handle == stats_table.handle
+        )
+    });
+    for (entity, class, size, class_tracker, combat, harvester, tank, healer) in
+        units.iter()
+    {
+        if !reloaded && !class_tracker.is_changed() {
+            continue;
+        }
+        let stats = match stats_table.get(*class, *size) {
+            Some(stats) => stats,
+            None => {
+                warn!("No stats for {}, check the stats file", class.name());
+                continue;
             }
+        };
+        // units that keep their roles keep their targets and carried
+        // bloodrock, the numbers get synced every frame anyway
+        let roles = [
+            combat.is_some(),
+            harvester.is_some(),
+            tank.is_some(),
+            healer.is_some(),
+        ];
+        if class_tracker.is_changed() || roles != stats.roles() {
+            change_class(entity, &mut cmd, stats);
         }
     }
 }
 
-pub fn change_class(
-    entity: Entity,
-    cmd: &mut Commands,
-    class: UnitClass,
-    health: &mut Health,
-) {
+fn change_class(entity: Entity, cmd: &mut Commands, stats: &UnitStats) {
     let mut entity_commands = cmd.entity(entity);
     entity_commands.remove::<CombatComponent>();
     entity_commands.remove::<Harvester>();
     entity_commands.remove::<TankComponent>();
     entity_commands.remove::<HealerComponent>();
+    entity_commands.remove::<Targeting>();
 
-    match class {
-        UnitClass::Worker => {
-            entity_commands.insert(Harvester {
-                target_node: None,
-                harvest_speed: Timer::from_seconds(1., false),
-                max_carryable_resource: 3,
-                current_carried_resource: 0,
-            });
-        }
-        UnitClass::Sworder => {
-            entity_commands.insert(CombatComponent {
-                target: None,
-                damage: 1.5,
-                time_between_attacks: Timer::from_seconds(1., true),
-                attack_range: 70.,
-                attack_type: AttackType::Melee,
-                attack_state: AttackState::NotAttacking,
-                target_type: UnitType::Enemy,
-                piercing: 0.,
-            });
-        }
-        UnitClass::Piker => {
-            entity_commands.insert(CombatComponent {
-                target: None,
-                damage: 1.5,
-                time_between_attacks: Timer::from_seconds(1.5, true),
-                attack_range: 100.,
-                attack_type: AttackType::Melee,
-                attack_state: AttackState::NotAttacking,
-                target_type: UnitType::Enemy,
-                piercing: 0.75,
-            });
-        }
-        UnitClass::Ranged => {
-            entity_commands.insert(CombatComponent {
+    if let Some(harvester) = &stats.harvester {
+        entity_commands.insert(Harvester {
+            target_node: None,
+            harvest_speed: Timer::from_seconds(harvester.harvest_time, false),
+            max_carryable_resource: harvester.capacity,
+            current_carried_resource: 0,
+        });
+    }
+    if let Some(combat) = &stats.combat {
+        entity_commands
+            .insert(CombatComponent {
                 target: None,
-                damage: 0.5,
-                time_between_attacks: Timer::from_seconds(1., true),
-                attack_range: 300.,
-                attack_type: AttackType::Ranged,
+                damage: combat.damage,
+                time_between_attacks: Timer::from_seconds(
+                    combat.time_between_attacks,
+                    true,
+                ),
+                attack_range: combat.attack_range,
+                attack_type: combat.attack_type,
                 attack_state: AttackState::NotAttacking,
                 target_type: UnitType::Enemy,
-                piercing: 0.2,
-            });
-        }
-        UnitClass::Tank => {
-            entity_commands
-                .insert(CombatComponent {
-                    target: None,
-                    damage: 0.5,
-                    time_between_attacks: Timer::from_seconds(2., true),
-                    attack_range: 70.,
-                    attack_type: AttackType::Melee,
-                    attack_state: AttackState::NotAttacking,
-                    target_type: UnitType::Enemy,
-                    piercing: 0.,
-                })
-                .insert(TankComponent {
-                    time_between_taunts: Timer::from_seconds(3., true),
-                    target_type: UnitType::Enemy,
-                });
-            health.max_health *= 2.;
-            health.current_health *= 2.;
-        }
-        UnitClass::Healer => {
-            entity_commands.insert(HealerComponent {
-                target: None,
-                time_between_heals: Timer::from_seconds(1.5, false),
-                range: 300.,
-                state: HealingState::Idle,
-                heal_amount: 0.5,
-                target_type: UnitType::Ally,
-            });
-        }
+                piercing: combat.piercing,
+            })
+            .insert(Targeting::new(combat.targeting));
     }
-}
-
-fn ally_targetting_logic_system(
-    mut allys: Query<(&mut CombatComponent, Entity), With<UnitFollowPlayer>>,
-    enemies: Query<Entity, With<BasicEnemyLogic>>,
-    transforms: Query<&GlobalTransform>,
-) {
-    //TODO: find closest enemy that the worker can attack
-    for (mut ally_combat, e) in allys.iter_mut() {
-        if ally_combat.target == None {
-            let mut ally_pos = Vec2::ZERO; //TODO: better error handling
-            if let Ok(ally_tr) = transforms.get(e) {
-                ally_pos = ally_tr.translation().truncate();
-            }
-            for enemy in enemies.iter() {
-                if let Ok(enemy_tr) = transforms.get(enemy) {
-                    if (ally_pos - enemy_tr.translation().truncate()).length()
-                        < ally_combat.attack_range.max(200.) + 100.
-                    {
-                        ally_combat.target = Some(enemy);
-                        break;
-                    }
-                }
-            }
-        }
+    if let Some(tank) = &stats.tank {
+        entity_commands.insert(TankComponent {
+            time_between_taunts: Timer::from_seconds(
+                tank.time_between_taunts,
+                true,
+            ),
+            target_type: UnitType::Enemy,
+        });
+    }
+    if let Some(healer) = &stats.healer {
+        entity_commands.insert(HealerComponent {
+            target: None,
+            time_between_heals: Timer::from_seconds(
+                healer.time_between_heals,
+                false,
+            ),
+            range: healer.range,
+            state: HealingState::Idle,
+            heal_amount: healer.heal_amount,
+            target_type: UnitType::Ally,
+        });
     }
 }
 
@@ -370,20 +500,46 @@ fn change_head_system(
     }
 }
 
+/// The class a unit merges as, with its size
+fn merge_class(
+    classes: &Query<(&UnitClass, &UnitSize, Option<&TierClass>)>,
+    entity: Entity,
+) -> Option<(ClassId, UnitSize)> {
+    classes.get(entity).ok().map(|(class, size, tier_class)| {
+        (ClassId::of(*class, tier_class), *size)
+    })
+}
+
 fn eat_other_worker_system(
     selected: Res<Selected>,
+    recipes: Res<MergeRecipes>,
     global_tr: Query<&GlobalTransform>,
+    classes: Query<(&UnitClass, &UnitSize, Option<&TierClass>)>,
     mut can_eat_workers: Query<(&mut CanEatWorker, Entity)>,
 ) {
     if let Some(selected_e) = selected.0 {
         if let Ok(selected_tr) = global_tr.get(selected_e) {
             let selected_entity_pos = selected_tr.translation();
+            let prey = merge_class(&classes, selected_e);
 
             for (mut can_eat, e) in can_eat_workers.iter_mut() {
                 can_eat.entity_to_eat = None;
                 if e == selected_e {
                     continue;
                 }
+                // only open up for units it has a recipe for
+                let mergeable =
+                    prey.as_ref().zip(merge_class(&classes, e)).map_or(
+                        false,
+                        |((prey_class, prey_size), (class, size))| {
+                            recipes
+                                .find((&class, size), (prey_class, *prey_size))
+                                .is_some()
+                        },
+                    );
+                if !mergeable {
+                    continue;
+                }
                 if let Ok(can_eat_tr) = global_tr.get(e) {
                     if (can_eat_tr.translation().truncate()
                         - selected_entity_pos.truncate())
@@ -402,42 +558,131 @@ fn eat_other_worker_system(
     }
 }
 
+/// Turns the formation towards where the player walks, G switches it
+fn formation_system(
+    input: Res<PlayerInput>,
+    time: Res<GameTime>,
+    mut formation: ResMut<Formation>,
+) {
+    if input.switch_formation {
+        formation.kind = formation.kind.next();
+        info!("switched to the {} formation", formation.kind.name());
+    }
+    let movement = Vec2::from(input.movement);
+    if movement == Vec2::ZERO {
+        return;
+    }
+    let direction = movement.normalize();
+    let turn = (time.delta_seconds() * FORMATION_TURN_SPEED).min(1.);
+    let facing = formation.facing.lerp(direction, turn).normalize_or_zero();
+    // turning around on the spot goes through zero
+    formation.facing = if facing == Vec2::ZERO {
+        direction
+    } else {
+        facing
+    };
+}
+
+fn reset_formation_system(mut formation: ResMut<Formation>) {
+    *formation = Formation::default();
+}
+
+/// Walks idle allies to their slot of the formation around the player
 fn player_follower_system(
     mut q_player_followers: Query<
-        (&mut Transform, &CombatComponent),
-        (With<UnitFollowPlayer>, Without<MouseFollow>),
+        (
+            Entity,
+            &mut Transform,
+            &UnitClass,
+            &Velocity,
+            Option<&CombatComponent>,
+            Option<&HealerComponent>,
+            Option<&Harvester>,
+        ),
+        (
+            With<UnitFollowPlayer>,
+            Without<MouseFollow>,
+            Without<UnitOrder>,
+        ),
     >,
     player: Query<&GlobalTransform, With<PlayerController>>,
+    formation: Res<Formation>,
     time: Res<GameTime>,
 ) {
-    for player_tr in player.iter() {
-        for (mut tr, cc) in q_player_followers.iter_mut() {
-            if matches!(cc.target, None) {
-                let direction_vector = player_tr.translation() - tr.translation;
+    let player_pos = match player.get_single() {
+        Ok(player_tr) => player_tr.translation().truncate(),
+        Err(_) => return,
+    };
+    let mut followers: Vec<(Entity, FormationRole)> = q_player_followers
+        .iter()
+        .map(|(e, _, class, ..)| (e, class.formation_role()))
+        .collect();
+    // older units get the slots closer to the player
+    followers.sort_by_key(|(e, _)| *e);
 
-                let direction_vector = direction_vector.truncate();
-                if direction_vector.length() < 500. {
-                    continue;
-                }
-                let direction_vector = direction_vector.normalize();
+    for role in [
+        FormationRole::Front,
+        FormationRole::Back,
+        FormationRole::Side,
+    ] {
+        let members: Vec<Entity> = followers
+            .iter()
+            .filter(|(_, r)| *r == role)
+            .map(|(e, _)| *e)
+            .collect();
+        for (index, e) in members.iter().enumerate() {
+            let (_, mut tr, _, vel, cc, healer, harvester) =
+                match q_player_followers.get_mut(*e) {
+                    Ok(follower) => follower,
+                    Err(_) => continue,
+                };
+            // fighting, healing and harvesting come first
+            let busy = cc.map_or(false, |cc| cc.target.is_some())
+                || healer.map_or(false, |healer| healer.target.is_some())
+                || harvester.map_or(false, |harvester| {
+                    harvester.target_node.is_some()
+                        || harvester.current_carried_resource
+                            == harvester.max_carryable_resource
+                });
+            if busy {
+                continue;
+            }
 
-                tr.translation +=
-                    direction_vector.extend(0.) * time.delta_seconds() * 150.;
+            let slot = formation.kind.slot(role, index, members.len());
+            let slot_pos = player_pos
+                + formation.facing * slot.x
+                + formation.facing.perp() * slot.y;
+            let to_slot = slot_pos - tr.translation.truncate();
+            let distance = to_slot.length();
+            if distance < SLOT_TOLERANCE {
+                continue;
             }
+            // catch up faster the further behind the unit is
+            let speed = (distance * 2.).clamp(vel.0, MAX_FOLLOW_SPEED);
+            let step = (speed * time.delta_seconds()).min(distance);
+            tr.translation += (to_slot / distance * step).extend(0.);
         }
     }
 }
 
 impl Plugin for WorkerLogicPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(SceneState::InGame)
-                .with_system(player_follower_system)
-                .with_system(eat_other_worker_system)
-                .with_system(change_head_system)
-                .with_system(ally_targetting_logic_system)
-                .with_system(set_stats_based_on_class_and_size_system)
-                .with_system(change_sprite_based_on_class_system),
-        );
+        app.init_resource::<Formation>()
+            .add_system_set(
+                SystemSet::on_enter(SceneState::LoadingLevel)
+                    .with_system(reset_formation_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(SceneState::InGame)
+                    .with_system(formation_system)
+                    .with_system(player_follower_system.after(formation_system))
+                    .with_system(eat_other_worker_system)
+                    .with_system(change_head_system)
+                    .with_system(
+                        apply_class_system.after(unit_stats_asset_event_system),
+                    )
+                    .with_system(set_stats_based_on_class_and_size_system)
+                    .with_system(change_sprite_based_on_class_system),
+            );
     }
 }